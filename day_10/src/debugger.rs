@@ -0,0 +1,283 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::{CycleHook, Instruction, Register, CPU};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    Equal,
+    Greater
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::Equal => "==",
+            Comparison::Greater => ">"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Breakpoint {
+    Cycle(isize),
+    /// Stops when the condition becomes true
+    Register { register: Register, comparison: Comparison, value: isize }
+}
+
+impl Breakpoint {
+    /// Parses `120` or conditions like `x > 10`, `x == 5` and `y<0`
+    fn parse(text: &str) -> Result<Breakpoint, String> {
+        let text: String = text.split_whitespace().collect();
+        if let Ok(cycle) = text.parse::<isize>() {
+            return Ok(Breakpoint::Cycle(cycle));
+        }
+        let split = text.find(['<', '>', '=']).ok_or(format!("Expected a cycle or a condition, got {text:?}"))?;
+        let register = Register::from_name(&text[..split]).ok_or(format!("Unknown register {:?}", &text[..split]))?;
+        let (comparison, value) = match &text[split..] {
+            rest if rest.starts_with("==") => (Comparison::Equal, &rest[2..]),
+            rest if rest.starts_with('=') => (Comparison::Equal, &rest[1..]),
+            rest if rest.starts_with('<') => (Comparison::Less, &rest[1..]),
+            rest => (Comparison::Greater, &rest[1..])
+        };
+        let value = value.parse::<isize>().map_err(|_| format!("Invalid value {value:?}"))?;
+        Ok(Breakpoint::Register { register, comparison, value })
+    }
+
+    fn holds(&self, cpu: &CPU) -> bool {
+        match self {
+            Breakpoint::Cycle(cycle) => cpu.cycle == *cycle,
+            Breakpoint::Register { register, comparison, value } => {
+                let current = cpu.registers[*register as usize];
+                match comparison {
+                    Comparison::Less => current < *value,
+                    Comparison::Equal => current == *value,
+                    Comparison::Greater => current > *value
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {cycle}"),
+            Breakpoint::Register { register, comparison, value } => {
+                write!(f, "{} {} {value}", register.name(), comparison.symbol())
+            }
+        }
+    }
+}
+
+/// How far to run before stopping again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Cycle,
+    /// Stops on the first cycle of an instruction after the one at `pc`
+    Instruction { pc: usize },
+    Continue,
+    /// Debugger is detached and the program runs to the end
+    Quit
+}
+
+/// Step debugger run as a cycle hook, reads commands from `input`
+pub struct Debugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    mode: Mode,
+    /// Breakpoints and whether their condition held on the previous cycle
+    breakpoints: Vec<(Breakpoint, bool)>,
+    /// Watched registers and their values on the previous cycle
+    watches: Vec<(Register, isize)>
+}
+
+pub const HELP: &str = "\
+Commands: enter or 's' steps a cycle, 'n' steps an instruction, 'c' continues, 'q' runs to the end
+          'b 120' or 'b x > 10' adds a breakpoint, 'd N' deletes breakpoint N
+          'w y' toggles a watch, 'i' lists breakpoints and watches, 'p' shows the CRT";
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// Stops on the first cycle and watches `x`
+    pub fn new(input: R, output: W) -> Debugger<R, W> {
+        Debugger {
+            input,
+            output,
+            mode: Mode::Cycle,
+            breakpoints: Vec::new(),
+            watches: vec![(Register::X, 1)]
+        }
+    }
+
+    fn print_status(&mut self, cpu: &CPU, instruction: &Instruction) {
+        let (row, col) = cpu.beam();
+        let registers: Vec<String> = Register::ALL.iter()
+            .map(|register| format!("{}={}", register.name(), cpu.registers[*register as usize]))
+            .collect();
+        writeln!(
+            self.output, "cycle {} pc {} {} | {} | beam row {row} col {col}",
+            cpu.cycle, cpu.pc, instruction, registers.join(" ")
+        ).unwrap();
+    }
+
+    /// Picture drawn so far with the beam marked below the current row
+    fn print_crt(&mut self, cpu: &CPU) {
+        let (_, col) = cpu.beam();
+        writeln!(self.output, "{}", cpu.picture()).unwrap();
+        writeln!(self.output, "{}^", " ".repeat(col as usize)).unwrap();
+    }
+
+    fn print_info(&mut self) {
+        for (index, (breakpoint, _)) in self.breakpoints.iter().enumerate() {
+            writeln!(self.output, "Breakpoint {}: {breakpoint}", index + 1).unwrap();
+        }
+        let watches: Vec<&str> = self.watches.iter().map(|(register, _)| register.name()).collect();
+        writeln!(self.output, "Watching: {}", watches.join(", ")).unwrap();
+    }
+
+    /// Reads commands until one of them resumes the program
+    fn prompt(&mut self, cpu: &CPU) {
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line).unwrap() == 0 {
+                self.mode = Mode::Quit;
+                return;
+            }
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            match command {
+                "" | "s" => self.mode = Mode::Cycle,
+                "n" => self.mode = Mode::Instruction { pc: cpu.pc },
+                "c" => self.mode = Mode::Continue,
+                "q" => self.mode = Mode::Quit,
+                "b" => {
+                    match Breakpoint::parse(argument) {
+                        Ok(breakpoint) => {
+                            let holds = breakpoint.holds(cpu);
+                            writeln!(self.output, "Breakpoint {}: {breakpoint}", self.breakpoints.len() + 1).unwrap();
+                            self.breakpoints.push((breakpoint, holds));
+                        },
+                        Err(e) => writeln!(self.output, "{e}").unwrap()
+                    }
+                    continue;
+                },
+                "d" => {
+                    match argument.parse::<usize>() {
+                        Ok(number) if (1..=self.breakpoints.len()).contains(&number) => {
+                            let (breakpoint, _) = self.breakpoints.remove(number - 1);
+                            writeln!(self.output, "Deleted breakpoint {number}: {breakpoint}").unwrap();
+                        },
+                        _ => writeln!(self.output, "No breakpoint {argument:?}").unwrap()
+                    }
+                    continue;
+                },
+                "w" => {
+                    match Register::from_name(argument) {
+                        Some(register) => match self.watches.iter().position(|(watched, _)| *watched == register) {
+                            Some(index) => {
+                                self.watches.remove(index);
+                            },
+                            None => self.watches.push((register, cpu.registers[register as usize]))
+                        },
+                        None => writeln!(self.output, "Unknown register {argument:?}").unwrap()
+                    }
+                    self.print_info();
+                    continue;
+                },
+                "i" => {
+                    self.print_info();
+                    continue;
+                },
+                "p" => {
+                    self.print_crt(cpu);
+                    continue;
+                },
+                _ => {
+                    writeln!(self.output, "Unknown command {line:?}\n{HELP}").unwrap();
+                    continue;
+                }
+            }
+            return;
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> CycleHook for Debugger<R, W> {
+    fn on_cycle(&mut self, cpu: &CPU, instruction: &Instruction) {
+        if self.mode == Mode::Quit {
+            return;
+        }
+        for (register, last) in self.watches.iter_mut() {
+            let current = cpu.registers[*register as usize];
+            if current != *last {
+                writeln!(self.output, "{}: {} -> {} on cycle {}", register.name(), last, current, cpu.cycle).unwrap();
+                *last = current;
+            }
+        }
+
+        let mut stop = match self.mode {
+            Mode::Cycle => true,
+            Mode::Instruction { pc } => cpu.pc != pc,
+            Mode::Continue | Mode::Quit => false
+        };
+        for (index, (breakpoint, held)) in self.breakpoints.iter_mut().enumerate() {
+            let holds = breakpoint.holds(cpu);
+            if holds && !*held {
+                writeln!(self.output, "Hit breakpoint {}: {breakpoint}", index + 1).unwrap();
+                stop = true;
+            }
+            *held = holds;
+        }
+        if stop {
+            self.print_status(cpu, instruction);
+            self.print_crt(cpu);
+            self.prompt(cpu);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, Crt, Schedule};
+
+    fn debug(program: &str, commands: &str) -> String {
+        let program = parse_program(program).unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let mut debugger = Debugger::new(commands.as_bytes(), &mut output);
+        CPU::new(Crt::default(), Schedule::default()).execute(&program, &mut [&mut debugger]).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        assert_eq!(Breakpoint::parse("x>10"), Ok(Breakpoint::Register {
+            register: Register::X, comparison: Comparison::Greater, value: 10
+        }));
+        assert_eq!(Breakpoint::parse(" 12 "), Ok(Breakpoint::Cycle(12)));
+        assert_eq!(Breakpoint::parse("q=1"), Err("Unknown register \"q\"".to_string()));
+
+        let program = "addx 3\nnoop\naddx -5\nset y 2\nnoop\nnoop";
+        let output = debug(program, "b x < 0\nb 5\nc\nc\nc\n");
+        let stops: Vec<&str> = output.lines().filter(|line| line.starts_with("cycle")).collect();
+        assert_eq!(stops, vec![
+            "cycle 1 pc 0 addx 3 | x=1 y=0 z=0 | beam row 0 col 0",
+            "cycle 5 pc 2 addx -5 | x=4 y=0 z=0 | beam row 0 col 4",
+            "cycle 6 pc 3 set y 2 | x=-1 y=0 z=0 | beam row 0 col 5",
+        ]);
+        assert!(output.contains("x: 1 -> 4 on cycle 3"));
+        assert!(output.contains("Hit breakpoint 1: x < 0"));
+        assert!(output.contains("##.##\n    ^"));
+    }
+
+    #[test]
+    fn test_stepping() {
+        let output = debug("addx 3\naddx 1\nnoop", "s\nn\nw y\nq\n");
+        let stops: Vec<&str> = output.lines().filter(|line| line.starts_with("cycle")).collect();
+        assert_eq!(stops.len(), 3);
+        assert!(stops[2].starts_with("cycle 3 pc 1"));
+        assert!(output.contains("Watching: x, y"));
+    }
+}
@@ -1,90 +1,486 @@
-use std::env;
+use std::{env, fmt, fs, io};
 
-// use log::debug;
+use log::debug;
 
 use utils::{read_input, set_logging_level};
 
+mod debugger;
+mod ocr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    X,
+    Y,
+    Z
+}
+
+impl Register {
+    const ALL: [Register; 3] = [Register::X, Register::Y, Register::Z];
+
+    fn from_name(name: &str) -> Option<Register> {
+        Register::ALL.into_iter().find(|register| register.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Register::X => "x",
+            Register::Y => "y",
+            Register::Z => "z"
+        }
+    }
+}
+
+/// Operand types of an instruction, a source is a register or a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OperandKind {
+    Register,
+    Source
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(Register),
+    Value(isize)
+}
+
+impl Operand {
+    fn parse(text: &str, kind: OperandKind) -> Result<Operand, String> {
+        if let Some(register) = Register::from_name(text) {
+            return Ok(Operand::Register(register));
+        }
+        match (kind, text.parse::<isize>()) {
+            (OperandKind::Source, Ok(value)) => Ok(Operand::Value(value)),
+            (OperandKind::Register, _) => Err(format!("Expected a register, got {text:?}")),
+            (OperandKind::Source, Err(_)) => Err(format!("Expected a register or a number, got {text:?}"))
+        }
+    }
+
+    fn value(&self, registers: &Registers) -> isize {
+        match self {
+            Operand::Register(register) => registers[*register as usize],
+            Operand::Value(value) => *value
+        }
+    }
+
+    /// Register index of a register operand, parsing makes sure the kind is right
+    fn register(&self) -> usize {
+        match self {
+            Operand::Register(register) => *register as usize,
+            Operand::Value(_) => unreachable!("Operand is not a register")
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "{}", register.name()),
+            Operand::Value(value) => write!(f, "{value}")
+        }
+    }
+}
+
+type Registers = [isize; Register::ALL.len()];
+
+struct InstructionSpec {
+    mnemonic: &'static str,
+    cycles: usize,
+    operands: &'static [OperandKind],
+    /// Applied to the registers after the last cycle, None on overflow
+    effect: fn(&mut Registers, &[Operand]) -> Option<()>
+}
+
+const INSTRUCTION_SET: [InstructionSpec; 6] = [
+    InstructionSpec { mnemonic: "noop", cycles: 1, operands: &[], effect: |_, _| Some(()) },
+    InstructionSpec {
+        mnemonic: "addx", cycles: 2, operands: &[OperandKind::Source],
+        effect: |r, o| {
+            r[Register::X as usize] = r[Register::X as usize].checked_add(o[0].value(r))?;
+            Some(())
+        }
+    },
+    InstructionSpec {
+        mnemonic: "add", cycles: 2, operands: &[OperandKind::Register, OperandKind::Source],
+        effect: |r, o| {
+            r[o[0].register()] = r[o[0].register()].checked_add(o[1].value(r))?;
+            Some(())
+        }
+    },
+    InstructionSpec {
+        mnemonic: "sub", cycles: 2, operands: &[OperandKind::Register, OperandKind::Source],
+        effect: |r, o| {
+            r[o[0].register()] = r[o[0].register()].checked_sub(o[1].value(r))?;
+            Some(())
+        }
+    },
+    InstructionSpec {
+        mnemonic: "mul", cycles: 4, operands: &[OperandKind::Register, OperandKind::Source],
+        effect: |r, o| {
+            r[o[0].register()] = r[o[0].register()].checked_mul(o[1].value(r))?;
+            Some(())
+        }
+    },
+    InstructionSpec {
+        mnemonic: "set", cycles: 1, operands: &[OperandKind::Register, OperandKind::Source],
+        effect: |r, o| {
+            r[o[0].register()] = o[1].value(r);
+            Some(())
+        }
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instruction {
+    /// Index in `INSTRUCTION_SET`
+    opcode: usize,
+    operands: Vec<Operand>
+}
+
+impl Instruction {
+    fn spec(&self) -> &'static InstructionSpec {
+        &INSTRUCTION_SET[self.opcode]
+    }
+
+    fn parse(line: &str) -> Result<Instruction, String> {
+        let split: Vec<&str> = line.split_whitespace().collect();
+        let opcode = INSTRUCTION_SET.iter()
+            .position(|spec| spec.mnemonic == split[0])
+            .ok_or(format!("Unknown instruction {:?}", split[0]))?;
+        let kinds = INSTRUCTION_SET[opcode].operands;
+        if split.len() - 1 != kinds.len() {
+            return Err(format!("{} takes {} operand(s), got {}", split[0], kinds.len(), split.len() - 1));
+        }
+        let operands = split[1..].iter()
+            .zip(kinds)
+            .map(|(text, kind)| Operand::parse(text, *kind))
+            .collect::<Result<Vec<Operand>, String>>()?;
+        Ok(Instruction { opcode, operands })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec().mnemonic)?;
+        for operand in &self.operands {
+            write!(f, " {operand}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Program line that cannot be parsed, `line` is the line number in the input file
+#[derive(Debug, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    reason: String
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.reason)
+    }
+}
+
+/// Instruction whose result doesn't fit in a register, `address` is its index
+/// in the program like in the disassembly
+#[derive(Debug, PartialEq, Eq)]
+struct ExecutionError {
+    address: usize,
+    cycle: isize,
+    instruction: String
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address {:04}, cycle {}: {} overflows", self.address, self.cycle, self.instruction)
+    }
+}
+
+/// Parses one instruction per line, blank lines and `;` comments are skipped
+fn parse_program(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    let mut program: Vec<Instruction> = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let code = line.split(';').next().unwrap().trim();
+        if code.is_empty() {
+            continue;
+        }
+        let instruction = Instruction::parse(code)
+            .map_err(|reason| ParseError { line: index + 1, reason })?;
+        program.push(instruction);
+    }
+    Ok(program)
+}
+
+/// Program listing with the address and cycles of each instruction as comments,
+/// the listing can be parsed back
+fn disassemble(program: &[Instruction]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut cycle: usize = 1;
+    for (address, instruction) in program.iter().enumerate() {
+        let cycles = instruction.spec().cycles;
+        let during = match cycles {
+            1 => format!("cycle {cycle}"),
+            _ => format!("cycles {}-{}", cycle, cycle + cycles - 1)
+        };
+        lines.push(format!("{:<12}; {address:04} {during}", instruction.to_string()));
+        cycle += cycles;
+    }
+    lines.join("\n")
+}
+
+/// Called during every cycle, after drawing and reporting
+trait CycleHook {
+    fn on_cycle(&mut self, cpu: &CPU, instruction: &Instruction);
+}
+
+/// Logs the registers on every cycle
+struct Trace;
+
+impl CycleHook for Trace {
+    fn on_cycle(&mut self, cpu: &CPU, instruction: &Instruction) {
+        println!("cycle {:>4} pc {:>4} {:<12} registers {:?}", cpu.cycle, cpu.pc, instruction.to_string(), cpu.registers);
+    }
+}
+
+/// Screen size in pixels and sprite width, the sprite is centered on `x`
+/// and even widths extend to the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Crt {
+    width: usize,
+    height: usize,
+    sprite_width: usize
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Crt { width: 40, height: 6, sprite_width: 3 }
+    }
+}
+
+/// Cycles to report the signal strength on, the default schedule never reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Schedule {
+    from: isize,
+    /// Zero reports only on `from`
+    every: isize
+}
+
+impl Schedule {
+    /// Parses `every 40 from 20`, `every 40` starts from the first interval
+    /// and `from 20` reports once
+    fn parse(text: &str) -> Result<Schedule, String> {
+        let split: Vec<&str> = text.split_whitespace().collect();
+        let mut schedule = Schedule::default();
+        let mut has_from = false;
+        for pair in split.chunks(2) {
+            let value = match pair {
+                [_, value] => value.parse::<isize>().map_err(|_| format!("Invalid number {value:?}"))?,
+                _ => return Err(format!("Expected a number after {:?}", pair[0]))
+            };
+            match pair[0] {
+                "every" if value > 0 => schedule.every = value,
+                "from" if value > 0 => {
+                    schedule.from = value;
+                    has_from = true;
+                },
+                "every" | "from" => return Err(format!("Expected a positive number, got {value}")),
+                other => return Err(format!("Expected 'every' or 'from', got {other:?}"))
+            }
+        }
+        if !has_from {
+            schedule.from = schedule.every;
+        }
+        if schedule.from == 0 {
+            return Err(format!("Empty schedule {text:?}"));
+        }
+        Ok(schedule)
+    }
+
+    fn contains(&self, cycle: isize) -> bool {
+        match self.every {
+            0 => cycle == self.from,
+            every => cycle >= self.from && (cycle - self.from) % every == 0
+        }
+    }
+}
+
 struct CPU {
     cycle: isize,
-    x_reg: isize,
-    report_on: Vec<isize>,
+    registers: Registers,
+    /// Index of the instruction being executed
+    pc: usize,
+    crt: Crt,
+    report_on: Schedule,
     signal_strength: Vec<isize>,
-    picture: String
+    /// Lit pixels row by row, the beam starts over from the top after the last row
+    pixels: Vec<bool>
 }
 
 impl CPU {
-    pub fn new(report_on: Vec<isize>) -> CPU {
+    pub fn new(crt: Crt, report_on: Schedule) -> CPU {
+        let mut registers: Registers = [0; Register::ALL.len()];
+        registers[Register::X as usize] = 1;
         CPU {
             cycle: 0,
-            x_reg: 1,
-            report_on: report_on,
+            registers,
+            pc: 0,
+            crt,
+            report_on,
             signal_strength: Vec::new(),
-            picture: String::new()
+            pixels: vec![false; crt.width * crt.height]
         }
     }
 
+    pub fn x_reg(&self) -> isize {
+        self.registers[Register::X as usize]
+    }
+
     fn tick(&mut self) {
-        if (self.cycle != 0) & (self.cycle % 40 == 0) {
-            self.picture.push('\n');
-        }
         self.draw();
 
         self.cycle += 1;
 
-        if self.report_on.contains(&self.cycle) {
+        if self.report_on.contains(self.cycle) {
             self.calculate_strength();
         }
     }
 
     fn draw(&mut self) {
-        let position: &isize = &self.cycle.rem_euclid(40);
-        if ((self.x_reg - 1)..=(self.x_reg + 1)).contains(position) {
-            self.picture.push('#');
-        } else {
-            self.picture.push('.');
-        }
+        let index = self.cycle.rem_euclid(self.pixels.len() as isize);
+        let position = index % self.crt.width as isize;
+        let left = self.x_reg() - (self.crt.sprite_width as isize - 1) / 2;
+        self.pixels[index as usize] = (left..left + self.crt.sprite_width as isize).contains(&position);
     }
 
-    fn calculate_strength(&mut self) {
-        self.signal_strength.push(self.cycle * self.x_reg);
+    /// Row and column of the pixel drawn during the current cycle
+    pub fn beam(&self) -> (isize, isize) {
+        let index = (self.cycle - 1).rem_euclid(self.pixels.len() as isize);
+        (index / self.crt.width as isize, index % self.crt.width as isize)
+    }
+
+    /// Rows drawn so far with `#` for lit pixels, the last row can be partial
+    pub fn picture(&self) -> String {
+        let drawn = (self.cycle.max(0) as usize).min(self.pixels.len());
+        self.pixels[..drawn]
+            .chunks(self.crt.width)
+            .map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Whole screen as a binary PBM image with lit pixels in black
+    pub fn picture_pbm(&self) -> Vec<u8> {
+        let mut image = format!("P4\n{} {}\n", self.crt.width, self.crt.height).into_bytes();
+        for row in self.pixels.chunks(self.crt.width) {
+            image.extend(row.chunks(8).map(|bits| {
+                bits.iter().enumerate().fold(0u8, |byte, (bit, lit)| byte | ((*lit as u8) << (7 - bit)))
+            }));
+        }
+        image
     }
 
-    fn noop(&mut self) {
-        self.tick();
+    fn calculate_strength(&mut self) {
+        self.signal_strength.push(self.cycle * self.x_reg());
     }
 
-    fn addx(&mut self, value: isize) {
-        self.tick();
-        self.tick();
-        self.x_reg += value;
+    /// Runs the instruction at `pc` through all of its cycles, the registers
+    /// are left as they were if the result overflows
+    pub fn step(&mut self, program: &[Instruction], hooks: &mut [&mut dyn CycleHook]) -> Result<(), ExecutionError> {
+        let instruction = &program[self.pc];
+        let spec = instruction.spec();
+        for _ in 0..spec.cycles {
+            self.tick();
+            for hook in hooks.iter_mut() {
+                hook.on_cycle(self, instruction);
+            }
+        }
+        if (spec.effect)(&mut self.registers, &instruction.operands).is_none() {
+            return Err(ExecutionError { address: self.pc, cycle: self.cycle, instruction: instruction.to_string() });
+        }
+        debug!("{instruction} -> {:?}", self.registers);
+        self.pc += 1;
+        Ok(())
     }
 
-    pub fn process_line(&mut self, line: &str) {
-        let split: Vec<&str> = line.split(" ").collect();
-        match split[0] {
-            "noop" => self.noop(),
-            "addx" => {
-                let value: isize = split[1].parse().unwrap();
-                self.addx(value);
-            },
-            &_ => panic!("Unknown command: {}", split[0])
+    pub fn execute(&mut self, program: &[Instruction], hooks: &mut [&mut dyn CycleHook]) -> Result<(), ExecutionError> {
+        while self.pc < program.len() {
+            self.step(program, hooks)?;
         }
+        Ok(())
     }
 }
 
+fn parse_option(args: &[String], name: &str) -> Option<usize> {
+    args.iter().position(|r| r == name).map(|pos| args[pos + 1].parse().unwrap())
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
     let input = read_input(&args);
+    let program = match parse_program(&input) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    if args.contains(&String::from("--disassemble")) {
+        println!("{}", disassemble(&program));
+        return;
+    }
 
-    let mut cpu = CPU::new(vec![20, 60, 100, 140, 180, 220]);
+    let default = Crt::default();
+    let crt = Crt {
+        width: parse_option(&args, "--width").unwrap_or(default.width),
+        height: parse_option(&args, "--height").unwrap_or(default.height),
+        sprite_width: parse_option(&args, "--sprite").unwrap_or(default.sprite_width)
+    };
+    if crt.width == 0 || crt.height == 0 {
+        eprintln!("Screen size must be positive, got {}x{}", crt.width, crt.height);
+        std::process::exit(1);
+    }
+    let schedule = match args.iter().position(|r| r == "--report") {
+        Some(pos) => Schedule::parse(&args[pos + 1]),
+        None => Schedule::parse("every 40 from 20")
+    };
+    let schedule = match schedule {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    for line in input.lines() {
-        cpu.process_line(line);
+    let mut cpu = CPU::new(crt, schedule);
+    let mut trace = Trace;
+    let mut debugger = debugger::Debugger::new(io::stdin().lock(), io::stdout());
+    let mut hooks: Vec<&mut dyn CycleHook> = Vec::new();
+    if args.contains(&String::from("--trace")) {
+        hooks.push(&mut trace);
+    }
+    if args.contains(&String::from("--debug")) {
+        println!("{}", debugger::HELP);
+        hooks.push(&mut debugger);
     }
+    if let Err(e) = cpu.execute(&program, &mut hooks) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+
     let sum_signal_strength: isize = cpu.signal_strength.iter().sum();
     println!("Sum signal strenghts: {}", sum_signal_strength);
-    println!("{}", cpu.picture);
+    let picture = cpu.picture();
+    if let Some(pos) = args.iter().position(|r| r == "--pbm") {
+        fs::write(&args[pos + 1], cpu.picture_pbm()).unwrap();
+        println!("Wrote {}", args[pos + 1]);
+    } else {
+        println!("{picture}");
+    }
+    match ocr::read(&picture) {
+        Ok(text) => println!("Letters: {text}"),
+        Err(e) => println!("{e}")
+    }
 }
 
 #[cfg(test)]
@@ -97,11 +493,10 @@ mod tests {
         set_logging_level(&args);
         let input = read_input(&args);
 
-        let mut cpu = CPU::new(vec![20, 60, 100, 140, 180, 220]);
+        let program = parse_program(&input).unwrap();
+        let mut cpu = CPU::new(Crt::default(), Schedule::parse("every 40 from 20").unwrap());
+        cpu.execute(&program, &mut []).unwrap();
 
-        for line in input.lines() {
-            cpu.process_line(line);
-        }
         let correct_picture = "\
         ##..##..##..##..##..##..##..##..##..##..\n\
         ###...###...###...###...###...###...###.\n\
@@ -110,6 +505,73 @@ mod tests {
         ######......######......######......####\n\
         #######.......#######.......#######.....".to_string();
 
-        assert_eq!(cpu.picture, correct_picture);
+        assert_eq!(cpu.picture(), correct_picture);
+        assert_eq!(cpu.signal_strength.iter().sum::<isize>(), 13140);
+
+        let pbm = cpu.picture_pbm();
+        assert_eq!(pbm.len(), "P4\n40 6\n".len() + 5 * 6);
+        assert_eq!(pbm[8..13], [0b11001100; 5]);
+    }
+
+    #[test]
+    fn test_crt_options() {
+        assert_eq!(Schedule::parse("every 40 from 20"), Ok(Schedule { from: 20, every: 40 }));
+        assert_eq!(Schedule::parse("every 10"), Ok(Schedule { from: 10, every: 10 }));
+        assert_eq!(Schedule::parse("from 7"), Ok(Schedule { from: 7, every: 0 }));
+        assert_eq!(Schedule::parse("every"), Err("Expected a number after \"every\"".to_string()));
+        assert!(Schedule::parse("each 5").is_err());
+        let schedule = Schedule::parse("every 40 from 20").unwrap();
+        let reported: Vec<isize> = (1..=240).filter(|cycle| schedule.contains(*cycle)).collect();
+        assert_eq!(reported, vec![20, 60, 100, 140, 180, 220]);
+
+        let program = parse_program("noop\naddx 2\nnoop\nnoop\nnoop\nnoop\nnoop").unwrap();
+        let crt = Crt { width: 4, height: 2, sprite_width: 1 };
+        let mut cpu = CPU::new(crt, Schedule::parse("every 3").unwrap());
+        cpu.execute(&program, &mut []).unwrap();
+        assert_eq!(cpu.picture(), ".#.#\n...#");
+        assert_eq!(cpu.signal_strength, vec![3, 18]);
+        assert_eq!(cpu.beam(), (1, 3));
+
+        // The beam starts over from the top, even sprites extend to the right
+        let crt = Crt { width: 3, height: 1, sprite_width: 2 };
+        let mut cpu = CPU::new(crt, Schedule::default());
+        cpu.execute(&parse_program("noop\nnoop\nset x 2\nnoop\nnoop").unwrap(), &mut []).unwrap();
+        assert_eq!(cpu.picture(), "..#");
+        assert_eq!(cpu.picture_pbm(), b"P4\n3 1\n\x20".to_vec());
+    }
+
+    #[test]
+    fn test_instruction_set() {
+        let program = parse_program("set y 3\naddx y ; x = 4\n\nmul y x\nsub z -2\nnoop").unwrap();
+        let listing = disassemble(&program);
+        assert_eq!(listing.lines().nth(2), Some("mul y x     ; 0002 cycles 4-7"));
+        assert_eq!(parse_program(&listing).unwrap(), program);
+
+        struct Cycles(Vec<(isize, isize)>);
+        impl CycleHook for Cycles {
+            fn on_cycle(&mut self, cpu: &CPU, _: &Instruction) {
+                self.0.push((cpu.cycle, cpu.x_reg()));
+            }
+        }
+        let mut cycles = Cycles(Vec::new());
+        let mut cpu = CPU::new(Crt::default(), Schedule::default());
+        cpu.execute(&program, &mut [&mut cycles]).unwrap();
+        assert_eq!(cpu.registers, [4, 12, 2]);
+        assert_eq!(cycles.0.len(), 1 + 2 + 4 + 2 + 1);
+        assert_eq!(cycles.0[2], (3, 1));
+        assert_eq!(cycles.0[3], (4, 4));
+
+        assert_eq!(parse_program("noop\naddx").unwrap_err(), ParseError {
+            line: 2,
+            reason: "addx takes 1 operand(s), got 0".to_string()
+        });
+        assert_eq!(parse_program("jmp 2").unwrap_err().reason, "Unknown instruction \"jmp\"");
+        assert_eq!(parse_program("set 1 2").unwrap_err().reason, "Expected a register, got \"1\"");
+
+        let program = parse_program(&format!("set x 2\n{}", "mul x x\n".repeat(7))).unwrap();
+        let mut cpu = CPU::new(Crt::default(), Schedule::default());
+        let error = cpu.execute(&program, &mut []).unwrap_err();
+        assert_eq!(error.to_string(), "Address 0006, cycle 25: mul x x overflows");
+        assert_eq!(cpu.x_reg(), 1 << 32);
     }
 }
@@ -0,0 +1,133 @@
+use std::fmt;
+
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 6;
+/// Glyphs are separated by one empty column
+const GLYPH_PITCH: usize = GLYPH_WIDTH + 1;
+
+/// Capital letters of the Advent of Code 4x6 font
+const FONT: [(char, [&str; GLYPH_HEIGHT]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', ["###.", ".#..", ".#..", ".#..", ".#..", "###."]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Glyph that is not in the font, `position` is the index of the letter
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownGlyph {
+    pub position: usize,
+    pub rows: Vec<String>
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OcrError {
+    /// Picture is not a row of 4x6 glyphs
+    Size { width: usize, height: usize },
+    /// `text` has `?` in place of the unknown glyphs
+    UnknownGlyphs { text: String, glyphs: Vec<UnknownGlyph> }
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::Size { width, height } => write!(
+                f, "Picture of {width}x{height} pixels is not a row of {GLYPH_WIDTH}x{GLYPH_HEIGHT} letters"
+            ),
+            OcrError::UnknownGlyphs { text, glyphs } => {
+                write!(f, "Read {text:?} with {} unknown glyph(s)", glyphs.len())?;
+                for glyph in glyphs {
+                    let column = glyph.position * GLYPH_PITCH;
+                    write!(f, "\nLetter {} at column {column}:\n{}", glyph.position + 1, glyph.rows.join("\n"))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the letters of a `#`/`.` picture, any other character than `#` is unlit
+pub fn read(picture: &str) -> Result<String, OcrError> {
+    let rows: Vec<Vec<bool>> = picture.lines()
+        .map(|line| line.chars().map(|c| c == '#').collect())
+        .collect();
+    let width = rows.first().map_or(0, |row| row.len());
+    let height = rows.len();
+    // The separator after the last letter is optional
+    let whole_letters = width.is_multiple_of(GLYPH_PITCH) || width % GLYPH_PITCH == GLYPH_WIDTH;
+    if height != GLYPH_HEIGHT || rows.iter().any(|row| row.len() != width) || width == 0 || !whole_letters {
+        return Err(OcrError::Size { width, height });
+    }
+
+    let mut text = String::new();
+    let mut unknown: Vec<UnknownGlyph> = Vec::new();
+    for position in 0..(width + 1) / GLYPH_PITCH {
+        let start = position * GLYPH_PITCH;
+        let glyph: Vec<String> = rows.iter()
+            .map(|row| row[start..start + GLYPH_WIDTH].iter().map(|lit| if *lit { '#' } else { '.' }).collect())
+            .collect();
+        match FONT.iter().find(|(_, pattern)| pattern.iter().zip(&glyph).all(|(a, b)| a == b)) {
+            Some((letter, _)) => text.push(*letter),
+            None if glyph.iter().all(|row| !row.contains('#')) => text.push(' '),
+            None => {
+                text.push('?');
+                unknown.push(UnknownGlyph { position, rows: glyph });
+            }
+        }
+    }
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::UnknownGlyphs { text, glyphs: unknown })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws letters the way the CRT does
+    fn draw(text: &str) -> String {
+        (0..GLYPH_HEIGHT)
+            .map(|row| {
+                text.chars()
+                    .map(|c| FONT.iter().find(|(letter, _)| *letter == c).map_or("....", |(_, rows)| rows[row]))
+                    .collect::<Vec<&str>>()
+                    .join(".")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_read() {
+        let alphabet: String = FONT.iter().map(|(letter, _)| *letter).collect();
+        assert_eq!(read(&draw(&alphabet)), Ok(alphabet));
+        assert_eq!(read(&draw("HI ZO")), Ok("HI ZO".to_string()));
+
+        let mut picture = draw("ABC");
+        picture.replace_range(0..1, "#");
+        match read(&picture) {
+            Err(OcrError::UnknownGlyphs { text, glyphs }) => {
+                assert_eq!(text, "?BC");
+                assert_eq!(glyphs[0].position, 0);
+                assert_eq!(glyphs[0].rows[0], "###.");
+            },
+            other => panic!("Expected an unknown glyph, got {other:?}")
+        }
+        assert_eq!(read("##\n##"), Err(OcrError::Size { width: 2, height: 2 }));
+    }
+}
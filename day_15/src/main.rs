@@ -9,6 +9,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 
 use utils::{read_input, set_logging_level};
+use utils::intervals::{Interval, IntervalSet};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct Sensor {
@@ -93,32 +94,25 @@ impl Map {
         return Some(RangeInclusive::new(start, end))
     }
 
-    fn covered(&self, y: i32) -> Vec<bool> {
+    /// Row indices covered by at least one sensor
+    fn covered(&self, y: i32) -> IntervalSet {
         debug!("y={}", y);
-        let mut row = vec![true; self.width() as usize];
-        debug!("{:?}", row.len());
-        for sensor in &self.sensors {
-            if let Some(sensor_range) = self.covers(sensor, &y) {
-                debug!("{:?}", sensor_range);
-                for i in sensor_range {
-                    row[i] = false;
-                }
-            }
-        }
-        return row;
+        IntervalSet::from_intervals(
+            self.sensors.iter()
+                .filter_map(|sensor| self.covers(sensor, &y))
+                .filter(|range| range.start() <= range.end())
+                .map(|range| Interval::new(*range.start() as i64, *range.end() as i64))
+        )
     }
 
     pub fn no_beacons(&self, y: i32) -> usize {
-        let mut row = self.covered(y);
+        let covered = self.covered(y);
+        debug!("{:?}", covered);
 
-        for beacon in &self.beacons {
-            if beacon.y == y {
-                row[(beacon.x + self.x_min.abs()) as usize] = true;
-            };
-        };
-        debug!("{:?}", &row);
-        debug!("{:?}", row.len());
-        return row.iter().filter(|&n| *n == false).count()
+        let beacons_on_row = self.beacons.iter()
+            .filter(|beacon| beacon.y == y && covered.contains((beacon.x + self.x_min.abs()) as i64))
+            .count();
+        covered.len() as usize - beacons_on_row
     }
 
 }
@@ -167,10 +161,10 @@ fn beacon_frequency(map: Map) -> Option<i32>{
 }
 
 fn find_in_range(map: Map, start: i32, end: i32) -> Option<[i32; 2]> {
+    let row = Interval::new(0, map.width() as i64 - 1);
     for y in start..=end {
-        let covered = map.covered(y);
-        if covered.contains(&true) {
-            let x: i32 = covered.iter().position(|&v| v == true).unwrap() as i32;
+        if let Some(gap) = map.covered(y).gaps(row).intervals().first() {
+            let x: i32 = gap.start as i32;
             debug!("Range {}..={}", start, end);
             debug!("Possible beacon x={} y={}", x, y);
             return Some([x, y]);
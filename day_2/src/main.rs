@@ -1,8 +1,11 @@
-use std::{env, str::FromStr, collections::HashMap};
+use std::{env, fs, str::FromStr, collections::HashMap, cmp::Reverse};
 
 use utils;
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy)]
 enum RPSSelect {
     Rock,
     Paper,
@@ -23,6 +26,8 @@ impl FromStr for RPSSelect {
 }
 
 impl RPSSelect {
+    const ALL: [RPSSelect; 3] = [RPSSelect::Rock, RPSSelect::Paper, RPSSelect::Scissors];
+
     pub fn from_result(opponent: &RPSSelect, result: &str) -> Self {
         match (opponent, result) {
             (&RPSSelect::Rock, "X") => RPSSelect::Scissors,
@@ -47,6 +52,15 @@ impl RPSSelect {
         }
     }
 
+    /// Selection that wins against this one
+    pub fn beaten_by(&self) -> Self {
+        match self {
+            RPSSelect::Rock => RPSSelect::Paper,
+            RPSSelect::Paper => RPSSelect::Scissors,
+            RPSSelect::Scissors => RPSSelect::Rock
+        }
+    }
+
     pub fn points(&mut self) -> i32 {
         match self {
             RPSSelect::Rock => 1,
@@ -105,8 +119,205 @@ impl RPSMatch {
     }
 }
 
+/// How my selection is made for each round of the strategy guide
+#[derive(Debug, Clone, PartialEq)]
+enum Strategy {
+    /// Play the selection written in the guide
+    Guide,
+    /// Always play the selection that beats the opponent
+    Optimal,
+    /// Pick randomly with weights for rock, paper and scissors
+    Mixed([f64; 3]),
+}
+
+impl Strategy {
+    pub fn uniform() -> Self {
+        Strategy::Mixed([1.0, 1.0, 1.0])
+    }
+
+    pub fn from_weights(s: &str) -> Result<Self, String> {
+        let weights: Vec<f64> = s.split(',')
+            .map(|w| w.trim().parse::<f64>().map_err(|_| format!("Invalid weight: {w}")))
+            .collect::<Result<_, _>>()?;
+        if weights.len() != 3 {
+            return Err(format!("Expected 3 weights, got {}", weights.len()));
+        }
+        if weights.iter().any(|w| *w < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+            return Err("Weights must be non-negative and not all zero".to_string());
+        }
+        Ok(Strategy::Mixed([weights[0], weights[1], weights[2]]))
+    }
+
+    fn select<R: Rng>(&self, round: &RPSMatch, rng: &mut R) -> RPSSelect {
+        match self {
+            Strategy::Guide => round.me,
+            Strategy::Optimal => round.opponent.beaten_by(),
+            Strategy::Mixed(weights) => {
+                let mut pick: f64 = rng.gen::<f64>() * weights.iter().sum::<f64>();
+                for (select, weight) in RPSSelect::ALL.iter().zip(weights) {
+                    if pick < *weight {
+                        return *select;
+                    }
+                    pick -= weight;
+                }
+                RPSSelect::Scissors
+            }
+        }
+    }
+
+    /// Expected points of a single round against the opponent
+    pub fn expected_points(&self, round: &RPSMatch) -> f64 {
+        match self {
+            Strategy::Mixed(weights) => {
+                let total: f64 = weights.iter().sum();
+                RPSSelect::ALL.iter().zip(weights)
+                    .map(|(select, weight)| {
+                        let points = RPSMatch { me: *select, opponent: round.opponent }.match_points();
+                        weight / total * points as f64
+                    })
+                    .sum()
+            },
+            _ => {
+                let me = self.select(round, &mut StdRng::seed_from_u64(0));
+                RPSMatch { me, opponent: round.opponent }.match_points() as f64
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Round {
+    opponent: RPSSelect,
+    me: RPSSelect,
+    points: i32
+}
+
+fn parse_guide(input: &str) -> Vec<RPSMatch> {
+    input.lines()
+        .map(|line| RPSMatch::from_str(line).unwrap())
+        .collect()
+}
+
+fn play_guide<R: Rng>(guide: &[RPSMatch], strategy: &Strategy, rng: &mut R) -> Vec<Round> {
+    guide.iter()
+        .map(|round| {
+            let me = strategy.select(round, rng);
+            let points = RPSMatch { me, opponent: round.opponent }.match_points();
+            Round { opponent: round.opponent, me, points }
+        })
+        .collect()
+}
+
+fn print_breakdown(rounds: &[Round]) {
+    let mut total: i32 = 0;
+    for (index, round) in rounds.iter().enumerate() {
+        total += round.points;
+        println!(
+            "Round {}: {:?} vs {:?} -> {} points (total {})",
+            index + 1, round.me, round.opponent, round.points, total
+        );
+    }
+}
+
+/// Player of a tournament, plays the second column of a strategy file in order
+#[derive(Debug)]
+struct Player {
+    name: String,
+    moves: Vec<RPSSelect>
+}
+
+impl Player {
+    pub fn from_guide(name: &str, input: &str) -> Self {
+        Player {
+            name: name.to_string(),
+            moves: parse_guide(input).into_iter().map(|round| round.me).collect()
+        }
+    }
+}
+
+/// Points each player scored in a single tournament round
+#[derive(Debug, PartialEq)]
+struct TournamentRound {
+    points: Vec<i32>
+}
+
+/// Every player plays every other player once per round, rounds run until
+/// the shortest strategy file is exhausted
+fn tournament(players: &[Player]) -> Vec<TournamentRound> {
+    let rounds = players.iter().map(|p| p.moves.len()).min().unwrap_or(0);
+    let mut results: Vec<TournamentRound> = Vec::new();
+
+    for round in 0..rounds {
+        let mut points = vec![0; players.len()];
+        for first in 0..players.len() {
+            for second in (first + 1)..players.len() {
+                let first_move = players[first].moves[round];
+                let second_move = players[second].moves[round];
+                points[first] += RPSMatch { me: first_move, opponent: second_move }.match_points();
+                points[second] += RPSMatch { me: second_move, opponent: first_move }.match_points();
+            }
+        }
+        results.push(TournamentRound { points });
+    }
+    results
+}
+
+fn parse_option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|r| r == name)
+        .and_then(|pos| args.get(pos + 1))
+        .map(|value| value.as_str())
+}
+
+fn parse_tournament_files(args: &[String]) -> Vec<String> {
+    match args.iter().position(|r| r == "--tournament") {
+        Some(pos) => args[pos + 1..].iter()
+            .take_while(|arg| !arg.starts_with("--"))
+            .cloned()
+            .collect(),
+        None => Vec::new()
+    }
+}
+
+fn run_tournament(files: &[String]) {
+    let players: Vec<Player> = files.iter()
+        .map(|file| {
+            let input = fs::read_to_string(file)
+                .unwrap_or_else(|_| panic!("Unable to read strategy file {file}"));
+            Player::from_guide(file, &input)
+        })
+        .collect();
+
+    let rounds = tournament(&players);
+    let mut totals = vec![0; players.len()];
+    for (index, round) in rounds.iter().enumerate() {
+        let scores: Vec<String> = players.iter()
+            .zip(&round.points)
+            .map(|(player, points)| format!("{}: {}", player.name, points))
+            .collect();
+        println!("Round {}: {}", index + 1, scores.join(", "));
+        for (total, points) in totals.iter_mut().zip(&round.points) {
+            *total += points;
+        }
+    }
+
+    let mut standings: Vec<(&Player, i32)> = players.iter().zip(totals).collect();
+    standings.sort_by_key(|standing| Reverse(standing.1));
+    println!("Tournament standings:");
+    for (place, (player, total)) in standings.iter().enumerate() {
+        println!("{}. {} {} points", place + 1, player.name, total);
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
+
+    let tournament_files = parse_tournament_files(&args);
+    if !tournament_files.is_empty() {
+        run_tournament(&tournament_files);
+        return;
+    }
+
     let input = utils::read_input(&args);
 
     let mut rps_match: RPSMatch;
@@ -124,4 +335,74 @@ fn main() {
         my_points_correct += rps_match.match_points();
     }
     println!("My points after correction: {}", my_points_correct);
+
+    // Optimiser and other strategies
+    let guide = parse_guide(&input);
+    let breakdown = args.contains(&String::from("--breakdown"));
+    let seed: u64 = match parse_option(&args, "--seed") {
+        Some(value) => value.parse().expect("Seed must be an integer"),
+        None => 0
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let strategy = if args.contains(&String::from("--random")) {
+        Strategy::uniform()
+    } else if let Some(weights) = parse_option(&args, "--mixed") {
+        Strategy::from_weights(weights).unwrap()
+    } else {
+        Strategy::Optimal
+    };
+
+    if breakdown {
+        println!("Strategy guide as written:");
+        print_breakdown(&play_guide(&guide, &Strategy::Guide, &mut rng));
+    }
+    let rounds = play_guide(&guide, &strategy, &mut rng);
+    if breakdown {
+        println!("{:?} strategy:", strategy);
+        print_breakdown(&rounds);
+    }
+    let points: i32 = rounds.iter().map(|round| round.points).sum();
+    println!("Points with {:?} strategy: {}", strategy, points);
+    if let Strategy::Mixed(_) = strategy {
+        let expected: f64 = guide.iter().map(|round| strategy.expected_points(round)).sum();
+        println!("Expected points with {:?} strategy: {:.2}", strategy, expected);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_optimal_strategy() {
+        let input = utils::read_input(&vec!["--test".to_string()]);
+        let guide = parse_guide(&input);
+        let rounds = play_guide(&guide, &Strategy::Optimal, &mut StdRng::seed_from_u64(0));
+        let points: i32 = rounds.iter().map(|round| round.points).sum();
+        // Paper vs rock, scissors vs paper, rock vs scissors
+        assert_eq!(points, 8 + 9 + 7);
+        assert_eq!(rounds[0], Round { opponent: RPSSelect::Rock, me: RPSSelect::Paper, points: 8 });
+    }
+
+    #[test]
+    fn test_expected_points_uniform() {
+        let round = RPSMatch { me: RPSSelect::Rock, opponent: RPSSelect::Rock };
+        // (1 + 3) + (2 + 6) + (3 + 0) over three selections
+        assert_eq!(Strategy::uniform().expected_points(&round), 5.0);
+        assert!(Strategy::from_weights("1,2").is_err());
+    }
+
+    #[test]
+    fn test_tournament() {
+        let players = vec![
+            Player { name: "rock".to_string(), moves: vec![RPSSelect::Rock, RPSSelect::Rock] },
+            Player { name: "paper".to_string(), moves: vec![RPSSelect::Paper, RPSSelect::Rock] },
+        ];
+        let rounds = tournament(&players);
+        assert_eq!(rounds, vec![
+            TournamentRound { points: vec![1, 8] },
+            TournamentRound { points: vec![4, 4] },
+        ]);
+    }
 }
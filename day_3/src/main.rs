@@ -1,8 +1,8 @@
 use std::env;
-use std::collections::{HashMap, HashSet};
-use std::hash::Hash;
+use std::fmt;
 
 use log::debug;
+use serde::Serialize;
 
 use utils::{read_input, set_logging_level};
 
@@ -13,63 +13,288 @@ static ALPHABET: [char; 52] = [
     'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z'
 ];
 
+#[derive(Debug, PartialEq, Eq)]
+enum RucksackError {
+    /// Line cannot be split into equally sized compartments
+    UnevenCompartments { line: usize, length: usize, compartments: usize },
+    InvalidItem { line: usize, item: char },
+    /// Last group has fewer rucksacks than the group size
+    IncompleteGroup { line: usize, rucksacks: usize, group_size: usize },
+    /// Groups must have at least one rucksack
+    EmptyGroup,
+}
+
+impl fmt::Display for RucksackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RucksackError::UnevenCompartments { line, length, compartments } => write!(
+                f, "Line {line}: {length} items cannot be split into {compartments} compartments"
+            ),
+            RucksackError::InvalidItem { line, item } => write!(
+                f, "Line {line}: invalid item {item:?}"
+            ),
+            RucksackError::IncompleteGroup { line, rucksacks, group_size } => write!(
+                f, "Line {line}: group has {rucksacks} rucksack(s), expected {group_size}"
+            ),
+            RucksackError::EmptyGroup => write!(f, "Group size must be at least 1"),
+        }
+    }
+}
+
+/// Set of items stored as a bitset, bit `n` is the item with priority `n`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const ALL: ItemSet = ItemSet(((1 << 52) - 1) << 1);
+
+    pub fn from_items(items: &str, line: usize) -> Result<ItemSet, RucksackError> {
+        let mut set = ItemSet::default();
+        for item in items.chars() {
+            set.0 |= 1 << priority(item).ok_or(RucksackError::InvalidItem { line, item })?;
+        }
+        Ok(set)
+    }
+
+    pub fn intersection(&self, other: &ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    pub fn union(&self, other: &ItemSet) -> ItemSet {
+        ItemSet(self.0 | other.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn contains(&self, priority: u32) -> bool {
+        self.0 & (1 << priority) != 0
+    }
+
+    pub fn priorities(&self) -> impl Iterator<Item = u32> + '_ {
+        (1..=52).filter(|p| self.contains(*p))
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = char> + '_ {
+        self.priorities().map(|p| ALPHABET[p as usize - 1])
+    }
+
+    pub fn priority_sum(&self) -> u32 {
+        self.priorities().sum()
+    }
+}
+
+/// Items found in every one of the sets
+fn common_items(sets: &[ItemSet]) -> ItemSet {
+    sets.iter().fold(ItemSet::ALL, |common, set| common.intersection(set))
+}
+
+fn priority(item: char) -> Option<u32> {
+    match item {
+        'a'..='z' => Some(item as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(item as u32 - 'A' as u32 + 27),
+        _ => None
+    }
+}
+
 #[derive(Debug)]
 struct Rucksack {
-    first: HashSet<char>,
-    second: HashSet<char>
+    compartments: Vec<ItemSet>
 }
 
 impl Rucksack {
-    pub fn from_string(input: &str) -> Self {
-        let lenght: usize = input.len();
-        let midpoint: usize = match lenght % 2 {
-            0 => lenght / 2,
-            _ => panic!("Compartments not evenly spaced")
-        };
-        debug!("lenght: {lenght}, midpoint: {midpoint}");
-        Rucksack {
-            first: input[..midpoint].chars().collect(),
-            second: input[midpoint..].chars().collect()
+    pub fn from_string(input: &str, compartments: usize, line: usize) -> Result<Self, RucksackError> {
+        // Validate items first so that compartments are split on character boundaries
+        ItemSet::from_items(input, line)?;
+        let length: usize = input.len();
+        if compartments == 0 || !length.is_multiple_of(compartments) {
+            return Err(RucksackError::UnevenCompartments { line, length, compartments });
         }
+        let size = length / compartments;
+        debug!("length: {length}, compartment size: {size}");
+        let compartments = (0..compartments)
+            .map(|i| ItemSet::from_items(&input[i * size..(i + 1) * size], line))
+            .collect::<Result<_, _>>()?;
+        Ok(Rucksack { compartments })
+    }
+
+    pub fn common_items(&self) -> ItemSet {
+        common_items(&self.compartments)
     }
 
-    pub fn common_items(&mut self) -> HashSet<&char> {
-        self.first.intersection(&self.second).collect()
+    pub fn all_items(&self) -> ItemSet {
+        self.compartments.iter().fold(ItemSet::default(), |all, set| all.union(set))
     }
 
-    pub fn priority_sum(&mut self, alphabet_index: &HashMap<char, i32>) -> i32 {
+    pub fn priority_sum(&self) -> u32 {
         let common_items = self.common_items();
-        debug!("common: {:?}", common_items);
-        let mut sum: i32 = 0;
+        debug!("common: {:?}", common_items.items().collect::<String>());
+        common_items.priority_sum()
+    }
+}
+
+fn parse_rucksacks(input: &str, compartments: usize) -> Result<Vec<Rucksack>, RucksackError> {
+    input.lines()
+        .enumerate()
+        .map(|(index, line)| Rucksack::from_string(line, compartments, index + 1))
+        .collect()
+}
 
-        for c in common_items {
-            sum += alphabet_index[c];
+/// Sum of items shared by every rucksack in each group of `group_size` rucksacks
+fn group_priority_sum(rucksacks: &[Rucksack], group_size: usize) -> Result<u32, RucksackError> {
+    if group_size == 0 {
+        return Err(RucksackError::EmptyGroup);
+    }
+    let mut sum: u32 = 0;
+    for (index, group) in rucksacks.chunks(group_size).enumerate() {
+        if group.len() != group_size {
+            return Err(RucksackError::IncompleteGroup {
+                line: index * group_size + 1,
+                rucksacks: group.len(),
+                group_size
+            });
         }
+        let items: Vec<ItemSet> = group.iter().map(|r| r.all_items()).collect();
+        sum += common_items(&items).priority_sum();
+    }
+    Ok(sum)
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct SharedItem {
+    item: char,
+    priority: u32,
+    /// Compartments or rucksacks the item was found in, starting from 1
+    found_in: Vec<usize>
+}
 
-        debug!("priority: {sum}");
-        debug!("---------------");
-        return sum
+#[derive(Debug, PartialEq, Serialize)]
+struct RucksackAudit {
+    line: usize,
+    shared: Vec<SharedItem>,
+    priority: u32,
+    warning: Option<String>
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct GroupAudit {
+    first_line: usize,
+    last_line: usize,
+    badges: Vec<SharedItem>,
+    priority: u32,
+    warning: Option<String>
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct AuditReport {
+    rucksacks: Vec<RucksackAudit>,
+    groups: Vec<GroupAudit>,
+    priority_sum: u32,
+    group_priority_sum: u32
+}
+
+/// Items found in more than one of the sets and which sets they were in
+fn shared_items(sets: &[ItemSet]) -> Vec<SharedItem> {
+    (1..=52)
+        .filter_map(|priority| {
+            let found_in: Vec<usize> = sets.iter()
+                .enumerate()
+                .filter(|(_, set)| set.contains(priority))
+                .map(|(index, _)| index + 1)
+                .collect();
+            match found_in.len() {
+                0 | 1 => None,
+                _ => Some(SharedItem { item: ALPHABET[priority as usize - 1], priority, found_in })
+            }
+        })
+        .collect()
+}
+
+/// Warning if there isn't exactly one item common to all of the sets
+fn common_item_warning(common: &ItemSet) -> Option<String> {
+    match common.len() {
+        0 => Some("no shared items".to_string()),
+        1 => None,
+        n => Some(format!("{n} shared items"))
     }
+}
+
+fn audit(rucksacks: &[Rucksack], group_size: usize) -> Result<AuditReport, RucksackError> {
+    let rucksack_audits: Vec<RucksackAudit> = rucksacks.iter()
+        .enumerate()
+        .map(|(index, rucksack)| {
+            let common = rucksack.common_items();
+            RucksackAudit {
+                line: index + 1,
+                shared: shared_items(&rucksack.compartments),
+                priority: common.priority_sum(),
+                warning: common_item_warning(&common)
+            }
+        })
+        .collect();
 
+    let group_priority_sum = group_priority_sum(rucksacks, group_size)?;
+    let groups: Vec<GroupAudit> = rucksacks.chunks(group_size)
+        .enumerate()
+        .map(|(index, group)| {
+            let items: Vec<ItemSet> = group.iter().map(|r| r.all_items()).collect();
+            let common = common_items(&items);
+            GroupAudit {
+                first_line: index * group_size + 1,
+                last_line: (index + 1) * group_size,
+                badges: shared_items(&items).into_iter()
+                    .filter(|shared| shared.found_in.len() == group_size)
+                    .collect(),
+                priority: common.priority_sum(),
+                warning: common_item_warning(&common)
+            }
+        })
+        .collect();
+
+    Ok(AuditReport {
+        priority_sum: rucksack_audits.iter().map(|r| r.priority).sum(),
+        rucksacks: rucksack_audits,
+        groups,
+        group_priority_sum
+    })
 }
 
-fn generate_alphabet_index() -> HashMap<char, i32> {
-    let mut index: i32 = 1;
-    let mut alphabet_index: HashMap<char, i32> = HashMap::new();
+fn format_shared(shared: &SharedItem, kind: &str) -> String {
+    let found_in: Vec<String> = shared.found_in.iter().map(|i| i.to_string()).collect();
+    format!("{} (priority {}) in {kind} {}", shared.item, shared.priority, found_in.join(", "))
+}
 
-    for c in ALPHABET {
-        alphabet_index.insert(c, index);
-        index += 1;
+fn print_audit(report: &AuditReport) {
+    for rucksack in &report.rucksacks {
+        let shared: Vec<String> = rucksack.shared.iter()
+            .map(|s| format_shared(s, "compartments"))
+            .collect();
+        print!("Line {}: {}", rucksack.line, shared.join("; "));
+        match &rucksack.warning {
+            Some(warning) => println!(" WARNING: {warning}"),
+            None => println!()
+        }
     }
-    return alphabet_index
+    for group in &report.groups {
+        let badges: Vec<String> = group.badges.iter()
+            .map(|s| format_shared(s, "rucksacks"))
+            .collect();
+        print!("Lines {}-{}: {}", group.first_line, group.last_line, badges.join("; "));
+        match &group.warning {
+            Some(warning) => println!(" WARNING: {warning}"),
+            None => println!()
+        }
+    }
+    println!("Total priority: {}", report.priority_sum);
+    println!("Total priority per group: {}", report.group_priority_sum);
 }
 
-fn dereference_hashmap<T>(hs: HashSet<&T>) -> HashSet<T> where T: Eq, T: Hash, T: Copy {
-    let mut new: HashSet<T> = HashSet::new();
-    for item in hs {
-        new.insert(*item);
-    };
-    new
+fn parse_option(args: &[String], name: &str, default: usize) -> usize {
+    match args.iter().position(|r| r == name) {
+        Some(pos) => args[pos + 1].parse::<usize>().unwrap(),
+        None => default
+    }
 }
 
 fn main() {
@@ -77,45 +302,98 @@ fn main() {
     set_logging_level(&args);
     let input = read_input(&args);
 
-    let alphabet_index = generate_alphabet_index();
+    let compartments = parse_option(&args, "--compartments", 2);
+    let group_size = parse_option(&args, "--group", 3);
 
-    let mut rucksack: Rucksack;
-    let mut priority_sum: i32 = 0;
+    let rucksacks = match parse_rucksacks(&input, compartments) {
+        Ok(rucksacks) => rucksacks,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    for line in input.lines() {
-        rucksack = Rucksack::from_string(&line);
-        priority_sum += rucksack.priority_sum(&alphabet_index)
+    if args.contains(&String::from("--audit")) {
+        match audit(&rucksacks, group_size) {
+            Ok(report) if args.contains(&String::from("--json")) => {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            },
+            Ok(report) => print_audit(&report),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
     }
+
+    let priority_sum: u32 = rucksacks.iter().map(|r| r.priority_sum()).sum();
     println!("Total priority: {priority_sum}");
 
     // Part 2
-    priority_sum = 0;
-    let mut input_lines = input.lines();
-    let mut first_elf: &str = input_lines.next().unwrap();
-    let mut second_elf: &str;
-    let mut third_elf: &str;
-
-    loop {
-        second_elf = input_lines.next().unwrap();
-        rucksack = Rucksack{
-            first: first_elf.chars().collect(),
-            second: second_elf.chars().collect()
-        };
-        // Make new rucksack based on 3rd elves items and common items from 1st and 2nd elves
-        let common_items = rucksack.common_items();
-        third_elf = input_lines.next().unwrap();
-        rucksack = Rucksack{
-            first: third_elf.chars().collect(),
-            second: dereference_hashmap(common_items)
-        };
-        priority_sum += rucksack.priority_sum(&alphabet_index);
-
-        // Are all lines processed?
-        let roll_over = input_lines.next();
-        if roll_over == None {
-            break;
+    match group_priority_sum(&rucksacks, group_size) {
+        Ok(sum) => println!("Total priority per {group_size} elves: {sum}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
         }
-        first_elf = roll_over.unwrap();
     }
-    println!("Total priority per 3 elves: {priority_sum}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_sums() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let rucksacks = parse_rucksacks(&input, 2).unwrap();
+        let sum: u32 = rucksacks.iter().map(|r| r.priority_sum()).sum();
+        assert_eq!(sum, 157);
+        assert_eq!(group_priority_sum(&rucksacks, 3), Ok(70));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        assert_eq!(
+            Rucksack::from_string("abc", 2, 4).unwrap_err(),
+            RucksackError::UnevenCompartments { line: 4, length: 3, compartments: 2 }
+        );
+        assert_eq!(
+            Rucksack::from_string("ab1c", 2, 1).unwrap_err(),
+            RucksackError::InvalidItem { line: 1, item: '1' }
+        );
+        let rucksacks = parse_rucksacks("abab\ncdcd", 2).unwrap();
+        assert_eq!(
+            group_priority_sum(&rucksacks, 3),
+            Err(RucksackError::IncompleteGroup { line: 1, rucksacks: 2, group_size: 3 })
+        );
+        assert_eq!(group_priority_sum(&rucksacks, 0), Err(RucksackError::EmptyGroup));
+        assert_eq!(audit(&rucksacks, 0).unwrap_err(), RucksackError::EmptyGroup);
+    }
+
+    #[test]
+    fn test_common_items_n_way() {
+        let rucksack = Rucksack::from_string("abcbcdcde", 3, 1).unwrap();
+        assert_eq!(rucksack.common_items().items().collect::<String>(), "c");
+        assert_eq!(common_items(&[]).items().count(), 52);
+    }
+
+    #[test]
+    fn test_audit() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let mut rucksacks = parse_rucksacks(&input, 2).unwrap();
+        rucksacks.push(Rucksack::from_string("abcd", 2, 7).unwrap());
+        rucksacks.push(Rucksack::from_string("abab", 2, 8).unwrap());
+        rucksacks.push(Rucksack::from_string("abcz", 2, 9).unwrap());
+        let report = audit(&rucksacks, 3).unwrap();
+
+        assert_eq!(report.rucksacks[0].shared, vec![SharedItem { item: 'p', priority: 16, found_in: vec![1, 2] }]);
+        assert_eq!(report.rucksacks[0].warning, None);
+        assert_eq!(report.rucksacks[6].warning, Some("no shared items".to_string()));
+        assert_eq!(report.rucksacks[7].warning, Some("2 shared items".to_string()));
+        assert_eq!(report.groups[0].badges, vec![SharedItem { item: 'r', priority: 18, found_in: vec![1, 2, 3] }]);
+        assert_eq!(report.groups[2].badges.len(), 2);
+        assert_eq!(report.priority_sum, 157 + 3);
+    }
 }
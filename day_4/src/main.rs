@@ -1,8 +1,11 @@
-use std::env;
+use std::{env, time::Instant};
 
 use log::debug;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 use utils::{read_input, set_logging_level};
+use utils::intervals::{Interval, IntervalSet, IntervalTree};
 
 #[derive(Debug)]
 struct IdRange {
@@ -30,35 +33,160 @@ impl IdRange {
         let cond2: bool = !(self.end < other.start);
         return cond1 & cond2;
     }
+
+    pub fn to_interval(&self) -> Interval {
+        Interval::new(self.start as i64, self.end as i64)
+    }
+}
+
+fn parse_assignments(input: &str) -> Vec<(IdRange, IdRange)> {
+    input.lines()
+        .map(|line| {
+            let split: Vec<&str> = line.split(',').collect();
+            (IdRange::from_string(split[0]), IdRange::from_string(split[1]))
+        })
+        .collect()
+}
+
+/// Section IDs within `bounds` covered by no one
+fn uncovered(ranges: &[Interval], bounds: Interval) -> IntervalSet {
+    IntervalSet::from_intervals(ranges.iter().copied()).gaps(bounds)
+}
+
+/// Section IDs covered by more than `k` elves
+fn overlapped(ranges: &[Interval], k: usize) -> IntervalSet {
+    match k.checked_add(1) {
+        Some(count) => IntervalSet::covered_at_least(ranges, count),
+        None => IntervalSet::new()
+    }
+}
+
+/// Elf `elf` (1 or 2) of the pair on line `line`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Assignment {
+    line: usize,
+    elf: usize
+}
+
+fn assignment_tree(assignments: &[(IdRange, IdRange)]) -> IntervalTree<Assignment> {
+    IntervalTree::new(
+        assignments.iter()
+            .enumerate()
+            .flat_map(|(index, (range1, range2))| [
+                (range1.to_interval(), Assignment { line: index + 1, elf: 1 }),
+                (range2.to_interval(), Assignment { line: index + 1, elf: 2 }),
+            ])
+            .collect()
+    )
+}
+
+fn naive_overlapping_pairs(ranges: &[Interval]) -> usize {
+    let mut count: usize = 0;
+    for (index, range) in ranges.iter().enumerate() {
+        count += ranges[index + 1..].iter().filter(|other| range.intersects(other)).count();
+    }
+    count
+}
+
+fn generate_ranges(count: usize, max_id: i64, seed: u64) -> Vec<Interval> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| {
+            let start = rng.gen_range(1..=max_id);
+            let end = rng.gen_range(start..=(start + 100).min(max_id));
+            Interval::new(start, end)
+        })
+        .collect()
+}
+
+/// Largest benchmark the quadratic pairwise scan is run on
+const NAIVE_LIMIT: usize = 200_000;
+
+/// Compares the interval tree to the naive pairwise scan on generated assignments
+fn benchmark(count: usize) {
+    let ranges = generate_ranges(count, count as i64 * 10, 0);
+    println!("Benchmark with {count} generated assignments");
+
+    let start = Instant::now();
+    let tree = IntervalTree::new(ranges.iter().map(|r| (*r, ())).collect());
+    let tree_pairs = tree.count_overlapping_pairs();
+    println!("Interval tree: {tree_pairs} overlapping pairs in {:?}", start.elapsed());
+
+    if count > NAIVE_LIMIT {
+        println!("Pairwise scan skipped, over {NAIVE_LIMIT} assignments");
+        return;
+    }
+    let start = Instant::now();
+    let naive_pairs = naive_overlapping_pairs(&ranges);
+    println!("Pairwise scan: {naive_pairs} overlapping pairs in {:?}", start.elapsed());
+}
+
+fn format_set(set: &IntervalSet) -> String {
+    let intervals: Vec<String> = set.intervals().iter().map(|i| i.to_string()).collect();
+    intervals.join(", ")
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
+
+    if let Some(pos) = args.iter().position(|r| r == "--bench") {
+        benchmark(args[pos + 1].parse().unwrap());
+        return;
+    }
+
     let input = read_input(&args);
 
     let mut fully_contained: i32 = 0;
     let mut has_intersection: i32 = 0;
-    let mut split: Vec<&str>;
-    let mut range1: IdRange;
-    let mut range2: IdRange;
-
-    for line in input.lines() {
-        split = line.split(",").collect();
-        range1 = IdRange::from_string(split[0]);
-        range2 = IdRange::from_string(split[1]);
+    let assignments = parse_assignments(&input);
 
-        if range1.contains(&range2) | range2.contains(&range1) {
+    for (range1, range2) in &assignments {
+        if range1.contains(range2) | range2.contains(range1) {
             fully_contained += 1;
         }
 
-        if range1.intersects(&range2) {
+        if range1.intersects(range2) {
             debug!("{:?} {:?}", range1, range2);
             has_intersection += 1;
         }
     }
     println!("Fully contained ranges: {fully_contained}");
     println!("Interecting ranges: {has_intersection}");
+
+    // Coverage over all assignments
+    let ranges: Vec<Interval> = assignments.iter()
+        .flat_map(|(range1, range2)| [range1.to_interval(), range2.to_interval()])
+        .collect();
+    let bounds: Interval = match args.iter().position(|r| r == "--bounds") {
+        Some(pos) => {
+            let range = IdRange::from_string(&args[pos + 1]);
+            range.to_interval()
+        },
+        None => Interval::new(
+            ranges.iter().map(|r| r.start).min().unwrap_or(0),
+            ranges.iter().map(|r| r.end).max().unwrap_or(0)
+        )
+    };
+    let k: usize = match args.iter().position(|r| r == "--overlap") {
+        Some(pos) => args[pos + 1].parse().unwrap(),
+        None => 1
+    };
+
+    let no_one = uncovered(&ranges, bounds);
+    println!("Section IDs in {bounds} covered by no one: {} [{}]", no_one.len(), format_set(&no_one));
+    let many = overlapped(&ranges, k);
+    println!("Section IDs covered by more than {k} elves: {} [{}]", many.len(), format_set(&many));
+
+    // Bulk overlap queries
+    let tree = assignment_tree(&assignments);
+    println!("Overlapping assignment pairs: {}", tree.count_overlapping_pairs());
+    if let Some(pos) = args.iter().position(|r| r == "--overlapping") {
+        let query = IdRange::from_string(&args[pos + 1]).to_interval();
+        for (interval, assignment) in tree.overlapping(&query) {
+            println!("Line {} elf {}: {interval}", assignment.line, assignment.elf);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -75,4 +203,33 @@ mod tests {
         assert!(r2.contains(&r2));
         assert!(!r2.contains(&r3));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_coverage() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let ranges: Vec<Interval> = parse_assignments(&input).iter()
+            .flat_map(|(range1, range2)| [range1.to_interval(), range2.to_interval()])
+            .collect();
+        assert_eq!(
+            uncovered(&ranges, Interval::new(1, 10)).intervals(),
+            &[Interval::new(1, 1), Interval::new(10, 10)]
+        );
+        assert_eq!(overlapped(&ranges, 6).intervals(), &[Interval::new(4, 6)]);
+        assert_eq!(overlapped(&ranges, 4).intervals(), &[Interval::new(3, 7)]);
+        assert!(overlapped(&ranges, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_assignment_tree() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let tree = assignment_tree(&parse_assignments(&input));
+        let found: Vec<Assignment> = tree.overlapping(&Interval::new(9, 9)).iter()
+            .map(|(_, assignment)| *assignment)
+            .collect();
+        assert_eq!(found, vec![Assignment { line: 3, elf: 2 }]);
+
+        let ranges = generate_ranges(500, 2000, 1);
+        let tree = IntervalTree::new(ranges.iter().map(|r| (*r, ())).collect());
+        assert_eq!(tree.count_overlapping_pairs(), naive_overlapping_pairs(&ranges));
+    }
+}
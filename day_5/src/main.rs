@@ -1,109 +1,522 @@
-use std::env;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+use std::{env, fmt, str::FromStr};
+use std::io::{self, BufRead};
 
 use log::debug;
 
 use utils::{read_input, set_logging_level};
 
-#[derive(Clone, Debug)]
+/// Move `n` crates from pile `from` to pile `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Move {
+    n: usize,
+    from: usize,
+    to: usize
+}
+
+impl FromStr for Move {
+    type Err = ();
+
+    /// Parses "move N from A to B"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split: Vec<&str> = s.split_whitespace().collect();
+        match split[..] {
+            ["move", n, "from", from, "to", to] => Ok(Move {
+                n: n.parse().map_err(|_| ())?,
+                from: from.parse().map_err(|_| ())?,
+                to: to.parse().map_err(|_| ())?
+            }),
+            _ => Err(())
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.n, self.from, self.to)
+    }
+}
+
+/// Invalid move, `line` is the line number in the input file and `yard` the
+/// drawing of the yard before the move
+#[derive(Debug, PartialEq, Eq)]
+enum MoveError {
+    Syntax { line: usize, text: String },
+    MissingPile { line: usize, pile: usize, yard: String },
+    NotEnoughCrates { line: usize, pile: usize, requested: usize, available: usize, yard: String },
+    SamePile { line: usize, pile: usize, yard: String }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::Syntax { line, text } => write!(
+                f, "Line {line}: expected \"move N from A to B\", got {text:?}"
+            ),
+            MoveError::MissingPile { line, pile, yard } => write!(
+                f, "Line {line}: pile {pile} doesn't exist\n{yard}"
+            ),
+            MoveError::NotEnoughCrates { line, pile, requested, available, yard } => write!(
+                f, "Line {line}: cannot move {requested} crate(s) from pile {pile} with {available} crate(s)\n{yard}"
+            ),
+            MoveError::SamePile { line, pile, yard } => write!(
+                f, "Line {line}: cannot move from pile {pile} to itself\n{yard}"
+            )
+        }
+    }
+}
+
+/// Parses the moves, `first_line` is the line number of the first move in the input file
+fn parse_moves(moves: &str, first_line: usize) -> Result<Vec<(usize, Move)>, MoveError> {
+    moves.lines()
+        .enumerate()
+        .map(|(index, text)| {
+            let line = first_line + index;
+            match Move::from_str(text) {
+                Ok(m) => Ok((line, m)),
+                Err(_) => Err(MoveError::Syntax { line, text: text.to_string() })
+            }
+        })
+        .collect()
+}
+
+/// Drawing of the yard that cannot be parsed, `line` is the line number in the input file
+#[derive(Debug, PartialEq, Eq)]
+struct YardError {
+    line: usize,
+    reason: String
+}
+
+impl fmt::Display for YardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.reason)
+    }
+}
+
+/// Character column range of a pile number or a crate in the drawing
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize
+}
+
+impl Span {
+    fn overlaps(&self, other: &Span) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+/// Finds the pile numbers and their columns from the number row, e.g. " 1   2   3 "
+fn parse_number_row(row: &str, line: usize) -> Result<Vec<Span>, YardError> {
+    let chars: Vec<char> = row.chars().collect();
+    let mut columns: Vec<Span> = Vec::new();
+    let mut index: usize = 0;
+    while index < chars.len() {
+        if chars[index].is_whitespace() {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < chars.len() && !chars[index].is_whitespace() {
+            index += 1;
+        }
+        let number: String = chars[start..index].iter().collect();
+        if number.parse::<usize>() != Ok(columns.len() + 1) {
+            return Err(YardError {
+                line,
+                reason: format!("expected pile number {}, got {number:?}", columns.len() + 1)
+            });
+        }
+        columns.push(Span { start, end: index - 1 });
+    }
+    if columns.is_empty() {
+        return Err(YardError { line, reason: "missing pile numbers".to_string() });
+    }
+    Ok(columns)
+}
+
+/// Finds the crates of a row as their label and column
+fn parse_crate_row(row: &str, line: usize) -> Result<Vec<(String, Span)>, YardError> {
+    let chars: Vec<char> = row.chars().collect();
+    let mut crates: Vec<(String, Span)> = Vec::new();
+    let mut index: usize = 0;
+    while index < chars.len() {
+        match chars[index] {
+            c if c.is_whitespace() => index += 1,
+            '[' => {
+                let start = index;
+                let end = match chars[start..].iter().position(|c| *c == ']') {
+                    Some(offset) => start + offset,
+                    None => return Err(YardError { line, reason: format!("unclosed crate at column {}", start + 1) })
+                };
+                let label: String = chars[start + 1..end].iter().collect();
+                if label.trim().is_empty() || label.contains('[') {
+                    return Err(YardError { line, reason: format!("invalid crate label at column {}", start + 1) });
+                }
+                crates.push((label, Span { start, end }));
+                index = end + 1;
+            },
+            c => return Err(YardError { line, reason: format!("unexpected {c:?} at column {}", index + 1) })
+        }
+    }
+    Ok(crates)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct ContainerYard {
-    piles: HashMap<u8, Vec<char>>
+    /// Piles from the first to the last, crates from the bottom to the top
+    piles: Vec<Vec<String>>
 }
 
 impl ContainerYard {
-    pub fn from_string(input: &str) -> ContainerYard{
-        let mut lines = input.lines();
-        // Get last number at the numbers line
-        // Expects " 1   2   3 "
-        let n_piles: u8 = lines
-            .next_back()
-            .unwrap()
-            .trim()
-            .split(" ")
-            .into_iter()
-            .last()
-            .unwrap()
-            .parse()
-            .unwrap();
-        debug!("N piles: {:?}", n_piles);
-
-        let mut container: char;
-        let mut piles: HashMap<u8, Vec<char>> = HashMap::new();
-        for i in 1..n_piles + 1 {
-            piles.insert(i, Vec::<char>::new());
-        }
-        // let container_row = lines.next_back().unwrap().as_bytes();
-        while let Some(container_row) = lines.next_back() {
-            for i in 0..n_piles {
-                container = container_row.as_bytes()[(1 + i * 4) as usize] as char;
-                if container == ' ' {
-                    continue;
+    /// Parses the drawing of the yard, crates are matched to the pile numbers
+    /// they line up with so labels can be wider than one character
+    pub fn from_string(input: &str) -> Result<ContainerYard, YardError> {
+        let lines: Vec<&str> = input.lines().collect();
+        let number_line = match lines.iter().rposition(|line| !line.trim().is_empty()) {
+            Some(index) => index,
+            None => return Err(YardError { line: 1, reason: "empty yard".to_string() })
+        };
+        let columns = parse_number_row(lines[number_line], number_line + 1)?;
+        debug!("N piles: {:?}", columns.len());
+
+        let mut piles: Vec<Vec<String>> = vec![Vec::new(); columns.len()];
+        for (index, row) in lines[..number_line].iter().enumerate().rev() {
+            let line = index + 1;
+            let height = number_line - index;
+            for (label, span) in parse_crate_row(row, line)? {
+                let overlapping: Vec<usize> = columns.iter()
+                    .enumerate()
+                    .filter(|(_, column)| column.overlaps(&span))
+                    .map(|(pile, _)| pile)
+                    .collect();
+                let pile = match overlapping[..] {
+                    [pile] => pile,
+                    [] => return Err(YardError {
+                        line,
+                        reason: format!("crate [{label}] at column {} isn't above a pile number", span.start + 1)
+                    }),
+                    [first, .., last] => return Err(YardError {
+                        line,
+                        reason: format!("crate [{label}] spans piles {} to {}", first + 1, last + 1)
+                    })
+                };
+                if piles[pile].len() != height - 1 {
+                    return Err(YardError {
+                        line,
+                        reason: format!("crate [{label}] isn't resting on pile {}", pile + 1)
+                    });
                 }
-                debug!("{}: {container}", i + 1);
-                piles.entry(i + 1).or_insert(Vec::<char>::new()).push(container);
+                debug!("{}: {label}", pile + 1);
+                piles[pile].push(label);
             }
         }
         debug!("{:?}", piles);
-        ContainerYard {piles}
+        Ok(ContainerYard { piles })
     }
 
-    pub fn move_with_string(&mut self, input: &str) {
-        let split: Vec<&str> = input.split(" ").collect();
-        let move_n: u8 = split[1].parse().unwrap();
-        let move_from: u8 = split[3].parse().unwrap();
-        let move_to: u8 = split[5].parse().unwrap();
-        debug!("Movement: {input}");
-        debug!("Pre :{:?}", self.piles);
-        for _ in 0..move_n {
-            let container: char = match self.piles.entry(move_from){
-                Entry::Occupied(mut v) => v.get_mut().pop().unwrap(),
-                Entry::Vacant(_) => panic!("Missing pile {move_from}")
-            };
-            match self.piles.entry(move_to){
-                Entry::Occupied(mut v) => v.get_mut().push(container),
-                Entry::Vacant(_) => panic!("Missing pile {move_from}")
-            };
-        }
-        debug!("Post:{:?}", self.piles)
+    /// Pile by its number, starting from 1
+    pub fn pile(&self, number: usize) -> Option<&Vec<String>> {
+        number.checked_sub(1).and_then(|index| self.piles.get(index))
     }
 
-    pub fn move_stack_with_string(&mut self, input: &str) {
-        let split: Vec<&str> = input.split(" ").collect();
-        let move_n: u8 = split[1].parse().unwrap();
-        let move_from: u8 = split[3].parse().unwrap();
-        let move_to: u8 = split[5].parse().unwrap();
-        debug!("Movement: {input}");
-        debug!("Pre :{:?}", self.piles);
+    /// Lifts `n` crates from the top of the pile, bottom crate first
+    fn take(&mut self, from: usize, n: usize) -> Vec<String> {
+        let pile = &mut self.piles[from - 1];
+        pile.split_off(pile.len() - n)
+    }
 
-        let mut container = match self.piles.entry(move_from){
-            Entry::Occupied(mut v) => {
-                let temp = v.get_mut();
-                temp.split_off(temp.len() - (move_n as usize))
-            },
-            Entry::Vacant(_) => panic!("Missing pile {move_from}")
-        };
-        match self.piles.entry(move_to){
-            Entry::Occupied(mut v) => v.get_mut().append(&mut container),
-            Entry::Vacant(_) => panic!("Missing pile {move_to}")
-        };
+    /// Puts crates on top of the pile, bottom crate first
+    fn put(&mut self, to: usize, mut crates: Vec<String>) {
+        self.piles[to - 1].append(&mut crates);
+    }
+
+    /// Checks that the move can be made in the current yard
+    pub fn check(&self, m: &Move, line: usize) -> Result<(), MoveError> {
+        for pile in [m.from, m.to] {
+            if self.pile(pile).is_none() {
+                return Err(MoveError::MissingPile { line, pile, yard: self.to_string() });
+            }
+        }
+        if m.from == m.to {
+            return Err(MoveError::SamePile { line, pile: m.from, yard: self.to_string() });
+        }
+        let available = self.piles[m.from - 1].len();
+        if available < m.n {
+            return Err(MoveError::NotEnoughCrates {
+                line, pile: m.from, requested: m.n, available, yard: self.to_string()
+            });
+        }
+        Ok(())
+    }
 
-        debug!("Post:{:?}", self.piles)
+    pub fn move_with_crane(&mut self, crane: &mut dyn Crane, m: &Move, line: usize) -> Result<(), MoveError> {
+        self.check(m, line)?;
+        debug!("Movement with {}: {m}", crane.name());
+        debug!("Pre :{:?}", self.piles);
+        crane.move_crates(self, m);
+        debug!("Post:{:?}", self.piles);
+        Ok(())
     }
 
     pub fn top_row(&self) -> String{
         let mut top_row = String::new();
-        let n_piles: &u8 = self.piles.keys().max().unwrap();
-        for i in 1..n_piles + 1 {
-            let pile: &Vec<char> = &self.piles[&i];
-            if pile.len() == 0 {
-                top_row.push('_');
-            } else {
-                top_row.push(pile[pile.len() - 1]);
+        for pile in &self.piles {
+            match pile.last() {
+                Some(container) => top_row.push_str(container),
+                None => top_row.push('_')
             }
         }
         debug!("{:?}", self);
-        return top_row;
+        top_row
+    }
+}
+
+/// Centers the text in a cell of `width` characters, extra space goes to the right
+fn center(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.chars().count());
+    format!("{}{text}{}", " ".repeat(padding / 2), " ".repeat(padding - padding / 2))
+}
+
+impl fmt::Display for ContainerYard {
+    /// Draws the yard in the same format it is parsed from
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Every pile is as wide as its widest crate or number
+        let widths: Vec<usize> = self.piles.iter()
+            .enumerate()
+            .map(|(index, pile)| {
+                let widest_crate = pile.iter().map(|c| c.chars().count() + 2).max().unwrap_or(0);
+                widest_crate.max(3).max((index + 1).to_string().len())
+            })
+            .collect();
+        let height: usize = self.piles.iter().map(|pile| pile.len()).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let row: Vec<String> = self.piles.iter()
+                .zip(&widths)
+                .map(|(pile, width)| match pile.get(level) {
+                    Some(container) => center(&format!("[{container}]"), *width),
+                    None => " ".repeat(*width)
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        let numbers: Vec<String> = widths.iter()
+            .enumerate()
+            .map(|(index, width)| center(&(index + 1).to_string(), *width))
+            .collect();
+        write!(f, "{}", numbers.join(" "))
+    }
+}
+
+/// Crane model that moves crates between the piles of a `ContainerYard`
+trait Crane {
+    fn name(&self) -> String;
+
+    fn move_crates(&mut self, yard: &mut ContainerYard, m: &Move);
+}
+
+/// Moves crates one at a time
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn move_crates(&mut self, yard: &mut ContainerYard, m: &Move) {
+        let mut crates = yard.take(m.from, m.n);
+        crates.reverse();
+        yard.put(m.to, crates);
+    }
+}
+
+/// Moves all crates at once
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn move_crates(&mut self, yard: &mut ContainerYard, m: &Move) {
+        let crates = yard.take(m.from, m.n);
+        yard.put(m.to, crates);
+    }
+}
+
+/// Moves at most `capacity` crates at once, bigger moves are split into several lifts
+struct LimitedCrane {
+    capacity: usize
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("Limited crane with capacity {}", self.capacity)
+    }
+
+    fn move_crates(&mut self, yard: &mut ContainerYard, m: &Move) {
+        let mut remaining = m.n;
+        while remaining > 0 {
+            let lift = remaining.min(self.capacity);
+            let crates = yard.take(m.from, lift);
+            yard.put(m.to, crates);
+            remaining -= lift;
+        }
+    }
+}
+
+/// Alternates between the two models on every move: the first, third and so on
+/// reverse the lifted crates like CrateMover 9000, the others keep their order
+/// like CrateMover 9001
+#[derive(Default)]
+struct AlternatingCrane {
+    moves: usize
+}
+
+impl Crane for AlternatingCrane {
+    fn name(&self) -> String {
+        "Alternating crane".to_string()
+    }
+
+    fn move_crates(&mut self, yard: &mut ContainerYard, m: &Move) {
+        let mut crates = yard.take(m.from, m.n);
+        if self.moves.is_multiple_of(2) {
+            crates.reverse();
+        }
+        self.moves += 1;
+        yard.put(m.to, crates);
+    }
+}
+
+/// Splits the input to the yard drawing and the moves
+fn split_input(input: &str) -> (&str, &str) {
+    // Input is expected to have Windows line endings, but allow Unix line endings as well
+    match input.split_once("\r\n\r\n") {
+        Some(split) => split,
+        None => input.split_once("\n\n").expect("Missing empty line after the yard")
+    }
+}
+
+fn run_crane(yard: &ContainerYard, crane: &mut dyn Crane, moves: &[(usize, Move)]) -> Result<ContainerYard, MoveError> {
+    let mut yard = yard.clone();
+    for (line, m) in moves {
+        yard.move_with_crane(crane, m, *line)?;
+    }
+    Ok(yard)
+}
+
+/// Yard after every move of a crane, stepped through with undo and redo.
+/// All moves are run up front so stateful cranes replay consistently.
+struct Replay {
+    frames: Vec<ContainerYard>,
+    moves: Vec<Move>,
+    position: usize
+}
+
+impl Replay {
+    pub fn record(yard: &ContainerYard, crane: &mut dyn Crane, moves: &[(usize, Move)]) -> Result<Replay, MoveError> {
+        let mut frames = vec![yard.clone()];
+        let mut yard = yard.clone();
+        for (line, m) in moves {
+            yard.move_with_crane(crane, m, *line)?;
+            frames.push(yard.clone());
+        }
+        Ok(Replay { frames, moves: moves.iter().map(|(_, m)| *m).collect(), position: 0 })
+    }
+
+    pub fn current(&self) -> &ContainerYard {
+        &self.frames[self.position]
+    }
+
+    /// Move that lead to the current yard
+    pub fn last_move(&self) -> Option<&Move> {
+        match self.position {
+            0 => None,
+            position => Some(&self.moves[position - 1])
+        }
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.position -= 1;
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if self.position + 1 >= self.frames.len() {
+            return false;
+        }
+        self.position += 1;
+        true
+    }
+
+    fn print_current(&self) {
+        match self.last_move() {
+            Some(m) => println!("Step {}/{}: {m}", self.position, self.moves.len()),
+            None => println!("Start, {} moves", self.moves.len())
+        }
+        println!("{}\n", self.current());
+    }
+
+    /// Prints the yard after every move
+    pub fn print_all(&mut self) {
+        self.position = 0;
+        self.print_current();
+        while self.redo() {
+            self.print_current();
+        }
+    }
+
+    /// Steps through the moves with commands read from stdin
+    pub fn interactive(&mut self) {
+        println!("Commands: enter or 'r' to redo, 'u' to undo, 'q' to quit");
+        self.print_current();
+        for line in io::stdin().lock().lines() {
+            let moved = match line.unwrap().trim() {
+                "" | "r" => self.redo(),
+                "u" => self.undo(),
+                "q" => break,
+                other => {
+                    println!("Unknown command {other:?}");
+                    continue;
+                }
+            };
+            if moved {
+                self.print_current();
+            } else {
+                println!("Nothing to do");
+            }
+        }
+    }
+}
+
+fn parse_crane(args: &[String]) -> Option<Box<dyn Crane>> {
+    let pos = args.iter().position(|r| r == "--crane")?;
+    let crane: Box<dyn Crane> = match args[pos + 1].as_str() {
+        "9000" => Box::new(CrateMover9000),
+        "9001" => Box::new(CrateMover9001),
+        "alternating" => Box::new(AlternatingCrane::default()),
+        "limited" => {
+            let capacity: usize = args[pos + 2].parse().unwrap();
+            if capacity == 0 {
+                panic!("Crane capacity must be at least 1");
+            }
+            Box::new(LimitedCrane { capacity })
+        },
+        other => panic!("Unknown crane {other}")
+    };
+    Some(crane)
+}
+
+/// Prints the error and exits instead of panicking
+fn exit_on_error<T, E: fmt::Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
     }
 }
 
@@ -111,20 +524,137 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
     let input = read_input(&args);
-    // Assumes Windows line endings with carriage return
-    let split: Vec<&str> = input.split("\r\n\r\n").collect();
-    let mut container_yard = ContainerYard::from_string(split[0]);
-    let mut container_yard_alt = container_yard.clone();
+    let (yard, moves) = split_input(&input);
+    let container_yard = exit_on_error(ContainerYard::from_string(yard));
+    // Moves start after the yard and the empty line
+    let moves = exit_on_error(parse_moves(moves, yard.lines().count() + 2));
 
-    for line in split[1].lines() {
-        container_yard.move_with_string(line);
-    };
-    let top_row = container_yard.top_row();
+    if args.contains(&"--replay".to_string()) || args.contains(&"--step".to_string()) {
+        let mut crane = parse_crane(&args).unwrap_or_else(|| Box::new(CrateMover9000));
+        let mut replay = exit_on_error(Replay::record(&container_yard, crane.as_mut(), &moves));
+        if args.contains(&"--step".to_string()) {
+            replay.interactive();
+        } else {
+            replay.print_all();
+        }
+        return;
+    }
+
+    if let Some(mut crane) = parse_crane(&args) {
+        let top_row = exit_on_error(run_crane(&container_yard, crane.as_mut(), &moves)).top_row();
+        println!("Top row with {}: {top_row}", crane.name());
+        return;
+    }
+
+    let top_row = exit_on_error(run_crane(&container_yard, &mut CrateMover9000, &moves)).top_row();
     println!("Top row: {top_row}");
 
-    for line in split[1].lines() {
-        container_yard_alt.move_stack_with_string(line);
-    };
-    let top_row_alt = container_yard_alt.top_row();
+    let top_row_alt = exit_on_error(run_crane(&container_yard, &mut CrateMover9001, &moves)).top_row();
     println!("Top row with CrateMover 9001: {top_row_alt}")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_test_input(crane: &mut dyn Crane) -> String {
+        let input = read_input(&vec!["--test".to_string()]);
+        let (yard, moves) = split_input(&input);
+        let moves = parse_moves(moves, 6).unwrap();
+        run_crane(&ContainerYard::from_string(yard).unwrap(), crane, &moves).unwrap().top_row()
+    }
+
+    #[test]
+    fn test_cranes() {
+        assert_eq!(run_test_input(&mut CrateMover9000), "CMZ");
+        assert_eq!(run_test_input(&mut CrateMover9001), "MCD");
+        // Capacity of one moves crates one at a time like CrateMover 9000
+        assert_eq!(run_test_input(&mut LimitedCrane { capacity: 1 }), "CMZ");
+        assert_eq!(run_test_input(&mut LimitedCrane { capacity: 3 }), "MCD");
+        assert_eq!(run_test_input(&mut LimitedCrane { capacity: 2 }), "MCZ");
+        // Moves 1 and 3 are reversed, moves 2 and 4 keep their order
+        assert_eq!(run_test_input(&mut AlternatingCrane::default()), "CMD");
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let (yard, _) = split_input(&input);
+        let rendered = ContainerYard::from_string(yard).unwrap().to_string();
+        assert_eq!(rendered, yard.replace("\r\n", "\n"));
+        assert_eq!(ContainerYard::from_string(&rendered).unwrap().to_string(), rendered);
+    }
+
+    #[test]
+    fn test_replay_undo_redo() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let (yard, moves) = split_input(&input);
+        let moves = parse_moves(moves, 6).unwrap();
+        let mut replay = Replay::record(&ContainerYard::from_string(yard).unwrap(), &mut CrateMover9000, &moves).unwrap();
+        assert!(!replay.undo());
+        while replay.redo() {}
+        assert_eq!(replay.current().top_row(), "CMZ");
+        assert_eq!(replay.last_move(), Some(&Move { n: 1, from: 1, to: 2 }));
+        assert!(replay.undo());
+        assert_eq!(replay.current().top_row(), "M_Z");
+    }
+
+    #[test]
+    fn test_invalid_moves() {
+        let yard = ContainerYard::from_string("[A]    \n 1   2 ").unwrap();
+        assert_eq!(
+            parse_moves("move 1 from 1 to 2\nmove one from 1 to 2", 4),
+            Err(MoveError::Syntax { line: 5, text: "move one from 1 to 2".to_string() })
+        );
+        let check = |text: &str| yard.check(&Move::from_str(text).unwrap(), 7);
+        assert_eq!(check("move 1 from 1 to 2"), Ok(()));
+        assert_eq!(
+            check("move 1 from 1 to 3"),
+            Err(MoveError::MissingPile { line: 7, pile: 3, yard: yard.to_string() })
+        );
+        assert_eq!(
+            check("move 1 from 2 to 2"),
+            Err(MoveError::SamePile { line: 7, pile: 2, yard: yard.to_string() })
+        );
+        assert_eq!(
+            check("move 2 from 1 to 2"),
+            Err(MoveError::NotEnoughCrates { line: 7, pile: 1, requested: 2, available: 1, yard: yard.to_string() })
+        );
+    }
+
+    #[test]
+    fn test_wide_yard() {
+        // Eleven piles, two character labels and no trailing whitespace
+        let drawing = [
+            format!("{}[AB]", " ".repeat(36)),
+            format!("[A]{}[I] [J]", " ".repeat(29)),
+            "[C] [B] [D] [E]     [G] [H] [Q] [R] [KL] [M]".to_string(),
+            " 1   2   3   4   5   6   7   8   9   10  11".to_string(),
+        ].join("\n");
+        let yard = ContainerYard::from_string(&drawing).unwrap();
+        assert_eq!(yard.piles.len(), 11);
+        assert_eq!(yard.pile(10), Some(&vec!["KL".to_string(), "J".to_string(), "AB".to_string()]));
+        assert_eq!(yard.top_row(), "ABDE_GHQIABM");
+        assert_eq!(ContainerYard::from_string(&yard.to_string()), Ok(yard));
+    }
+
+    #[test]
+    fn test_invalid_yard() {
+        assert_eq!(
+            ContainerYard::from_string("[A]\n 1   3 ").unwrap_err(),
+            YardError { line: 2, reason: "expected pile number 2, got \"3\"".to_string() }
+        );
+        assert_eq!(
+            ContainerYard::from_string("    [A]\n[B]    \n 1   2 ").unwrap_err(),
+            YardError { line: 1, reason: "crate [A] isn't resting on pile 2".to_string() }
+        );
+        assert_eq!(
+            ContainerYard::from_string("      [A]\n 1   2 ").unwrap_err(),
+            YardError { line: 1, reason: "crate [A] at column 7 isn't above a pile number".to_string() }
+        );
+        assert_eq!(
+            ContainerYard::from_string("[WIDE]\n 1   2 ").unwrap_err(),
+            YardError { line: 1, reason: "crate [WIDE] spans piles 1 to 2".to_string() }
+        );
+    }
+}
@@ -1,60 +1,344 @@
-use std::env;
-use std::io::{BufReader, Read};
+use std::{env, time::Instant};
+use std::io::{self, BufRead, BufReader, Read};
 use std::fs::File;
-use std::collections::{VecDeque, HashSet};
+use std::hash::Hash;
+use std::collections::{VecDeque, HashMap, HashSet};
 
 use log::debug;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 use utils::{parse_input_file_path, set_logging_level};
 
+/// Unit of the stream markers are made of
+trait Symbol: Copy + Eq + Hash {
+    fn is_line_end(&self) -> bool;
 
-fn all_unique(buffer: &VecDeque<char>) -> bool {
-    let mut set: HashSet<&char> = HashSet::new();
-    for c in buffer {
-        set.insert(c);
+    fn is_whitespace(&self) -> bool;
+}
+
+impl Symbol for u8 {
+    fn is_line_end(&self) -> bool {
+        *self == b'\r' || *self == b'\n'
+    }
+
+    fn is_whitespace(&self) -> bool {
+        self.is_ascii_whitespace()
     }
-    return set.len() == buffer.len();
 }
 
-fn parse_size(args: &Vec<String>) -> usize {
-    match args.iter().position(|r| r == "--size") {
-        Some(pos) => {
-            args[pos + 1].parse::<usize>().unwrap()
-        },
-        None => 4
+impl Symbol for char {
+    fn is_line_end(&self) -> bool {
+        *self == '\r' || *self == '\n'
+    }
+
+    fn is_whitespace(&self) -> bool {
+        char::is_whitespace(*self)
+    }
+}
+
+/// Decodes Unicode scalar values from a stream of UTF-8 bytes
+struct Utf8Chars<R: BufRead> {
+    bytes: io::Bytes<R>
+}
+
+impl<R: BufRead> Utf8Chars<R> {
+    pub fn new(reader: R) -> Utf8Chars<R> {
+        Utf8Chars { bytes: reader.bytes() }
     }
 }
 
+impl<R: BufRead> Iterator for Utf8Chars<R> {
+    type Item = io::Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.bytes.next()? {
+            Ok(b) => b,
+            Err(e) => return Some(Err(e))
+        };
+        let length = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0
+        };
+        let mut buffer = [first, 0, 0, 0];
+        let mut read = 1;
+        while read < length {
+            match self.bytes.next() {
+                Some(Ok(b)) => buffer[read] = b,
+                Some(Err(e)) => return Some(Err(e)),
+                None => break
+            }
+            read += 1;
+        }
+        match std::str::from_utf8(&buffer[..read]) {
+            Ok(s) if length > 0 => Some(Ok(s.chars().next().unwrap())),
+            _ => Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid UTF-8 sequence {:?}", &buffer[..read])
+            )))
+        }
+    }
+}
+
+/// Whether markers are made of bytes or Unicode scalar values
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Bytes,
+    Unicode
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MarkerOptions {
+    mode: Mode,
+    /// Skip whitespace and line endings instead of stopping at the first line end
+    ignore_whitespace: bool
+}
+
+impl Default for MarkerOptions {
+    fn default() -> Self {
+        MarkerOptions { mode: Mode::Bytes, ignore_whitespace: false }
+    }
+}
+
+/// Sliding window over the last `size` symbols, keeps a count of every
+/// symbol in the window so that each step is O(1)
+struct MarkerWindow<T: Symbol> {
+    size: usize,
+    buffer: VecDeque<T>,
+    counts: HashMap<T, usize>,
+    distinct: usize
+}
+
+impl<T: Symbol> MarkerWindow<T> {
+    pub fn new(size: usize) -> MarkerWindow<T> {
+        MarkerWindow {
+            size,
+            buffer: VecDeque::with_capacity(size + 1),
+            counts: HashMap::new(),
+            distinct: 0
+        }
+    }
+
+    /// Adds a symbol to the window, returns true if the window is a marker
+    pub fn push(&mut self, c: T) -> bool {
+        self.buffer.push_back(c);
+        let count = self.counts.entry(c).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            self.distinct += 1;
+        }
+
+        if self.buffer.len() > self.size {
+            let removed = self.buffer.pop_front().unwrap();
+            let count = self.counts.get_mut(&removed).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.distinct -= 1;
+            }
+        }
+        self.distinct == self.size
+    }
+}
+
+/// Markers of a single size, offsets are the number of symbols read when the
+/// marker ends, skipped whitespace included
+#[derive(Debug, PartialEq)]
+struct Markers {
+    size: usize,
+    offsets: Vec<usize>
+}
+
+/// Finds every marker of each size in a single pass over the stream
+fn find_markers<R: Read>(reader: R, sizes: &[usize], options: &MarkerOptions) -> io::Result<Vec<Markers>> {
+    let reader = BufReader::new(reader);
+    match options.mode {
+        Mode::Bytes => scan(reader.bytes(), sizes, options.ignore_whitespace),
+        Mode::Unicode => scan(Utf8Chars::new(reader), sizes, options.ignore_whitespace)
+    }
+}
+
+fn scan<T, I>(symbols: I, sizes: &[usize], ignore_whitespace: bool) -> io::Result<Vec<Markers>>
+where T: Symbol, I: Iterator<Item = io::Result<T>> {
+    let mut windows: Vec<MarkerWindow<T>> = sizes.iter().map(|size| MarkerWindow::new(*size)).collect();
+    let mut markers: Vec<Markers> = sizes.iter()
+        .map(|size| Markers { size: *size, offsets: Vec::new() })
+        .collect();
+
+    for (index, symbol) in symbols.enumerate() {
+        let c: T = symbol?;
+        if ignore_whitespace && c.is_whitespace() {
+            continue;
+        }
+        // Break at CR / LF
+        if c.is_line_end() {
+            break;
+        }
+
+        for (window, found) in windows.iter_mut().zip(markers.iter_mut()) {
+            if window.push(c) {
+                debug!("Marker of size {} ends at {}", window.size, index + 1);
+                found.offsets.push(index + 1);
+            }
+        }
+    }
+    Ok(markers)
+}
+
+/// Checks every window separately, used as the baseline in benchmarks
+fn naive_first_marker(stream: &[u8], size: usize) -> Option<usize> {
+    (size..=stream.len())
+        .find(|end| stream[end - size..*end].iter().collect::<HashSet<&u8>>().len() == size)
+}
+
+fn generate_stream(megabytes: usize, alphabet: &[char], seed: u64) -> String {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut stream = String::with_capacity(megabytes * 1024 * 1024);
+    while stream.len() < megabytes * 1024 * 1024 {
+        stream.push(alphabet[rng.gen_range(0..alphabet.len())]);
+    }
+    stream
+}
+
+/// Times the marker search on generated streams, the alphabets are too small
+/// for markers of size 14 so the whole stream is read
+fn benchmark(megabytes: usize) -> io::Result<()> {
+    let ascii: Vec<char> = "abcdefghijklm".chars().collect();
+    let greek: Vec<char> = "αβγδεζηθικλμν".chars().collect();
+    let sizes = [4, 14];
+
+    let stream = generate_stream(megabytes, &ascii, 0);
+    println!("ASCII stream of {} bytes", stream.len());
+    let options = MarkerOptions::default();
+    let start = Instant::now();
+    let markers = find_markers(stream.as_bytes(), &sizes, &options)?;
+    println!("Byte mode: {} marker(s) in {:?}", markers[0].offsets.len(), start.elapsed());
+
+    let options = MarkerOptions { mode: Mode::Unicode, ..options };
+    let start = Instant::now();
+    let markers = find_markers(stream.as_bytes(), &sizes, &options)?;
+    println!("Unicode mode: {} marker(s) in {:?}", markers[0].offsets.len(), start.elapsed());
+
+    let start = Instant::now();
+    let first = naive_first_marker(stream.as_bytes(), 14);
+    println!("Window by window search for size 14: {:?} in {:?}", first, start.elapsed());
+
+    let stream = generate_stream(megabytes, &greek, 0);
+    println!("Greek stream of {} bytes", stream.len());
+    let start = Instant::now();
+    let markers = find_markers(stream.as_bytes(), &sizes, &options)?;
+    println!("Unicode mode: {} marker(s) in {:?}", markers[0].offsets.len(), start.elapsed());
+    Ok(())
+}
+
+fn parse_sizes(args: &[String]) -> Result<Vec<usize>, String> {
+    let pos = match args.iter().position(|r| r == "--size") {
+        Some(pos) => pos,
+        None => return Ok(vec![4, 14])
+    };
+    let list = args.get(pos + 1).ok_or("Missing marker lengths after --size")?;
+    list.split(',')
+        .map(|size| match size.parse::<usize>() {
+            Ok(0) => Err("Marker length must be at least 1".to_string()),
+            Ok(size) => Ok(size),
+            Err(_) => Err(format!("Invalid marker length {size:?}"))
+        })
+        .collect()
+}
+
 
 fn main() -> std::io::Result<()> {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
-    let filepath = parse_input_file_path(&args);
 
-    let mut char_buffer: VecDeque<char> = VecDeque::new();
-    let size: usize = parse_size(&args);
-    let file = BufReader::new(File::open(filepath)?);
-    println!("Set marker lenght with '--size N'");
-    println!("Marker lenght: {size}");
+    if let Some(pos) = args.iter().position(|r| r == "--bench") {
+        return benchmark(args[pos + 1].parse().unwrap());
+    }
 
-    for (index, byte) in file.bytes().enumerate() {
-        let b: u8 = byte.unwrap();
-        // Break at CR / LF
-        if (b == 13) | (b == 10) {
-            break;
+    let sizes: Vec<usize> = match parse_sizes(&args) {
+        Ok(sizes) => sizes,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
         }
-        let c: char = b as char;
+    };
+    let options = MarkerOptions {
+        mode: if args.contains(&String::from("--unicode")) { Mode::Unicode } else { Mode::Bytes },
+        ignore_whitespace: args.contains(&String::from("--ignore-whitespace"))
+    };
+    println!("Set marker lenghts with '--size N,M'");
+    println!("Marker lenghts: {sizes:?}");
+
+    let markers = if args.contains(&String::from("--stdin")) {
+        find_markers(io::stdin().lock(), &sizes, &options)?
+    } else {
+        let filepath = parse_input_file_path(&args);
+        find_markers(File::open(filepath)?, &sizes, &options)?
+    };
 
-        if char_buffer.len() == size {
-            char_buffer.pop_front();
+    for found in markers {
+        match found.offsets.first() {
+            Some(offset) => println!("Marker of size {} starts message at: {}", found.size, offset),
+            None => println!("No marker of size {} found", found.size)
         }
-        char_buffer.push_back(c);
-        debug!("{:?}", char_buffer);
-        if all_unique(&char_buffer) & (char_buffer.len() == size) {
-            println!("Message starts at: {}", index + 1);
-            break;
+        if args.contains(&String::from("--all")) {
+            println!("All {} marker(s) of size {}: {:?}", found.offsets.len(), found.size, found.offsets);
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_markers() {
+        let markers = find_markers(
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb\n".as_bytes(), &[4, 14], &MarkerOptions::default()
+        ).unwrap();
+        assert_eq!(markers[0].offsets[0], 7);
+        assert_eq!(markers[1].offsets[0], 19);
+        // Every window of four after the first marker that has no repeats
+        assert_eq!(&markers[0].offsets[..4], &[7, 8, 9, 10]);
+
+        let markers = find_markers("abcabc".as_bytes(), &[3, 4], &MarkerOptions::default()).unwrap();
+        assert_eq!(markers, vec![
+            Markers { size: 3, offsets: vec![3, 4, 5, 6] },
+            Markers { size: 4, offsets: vec![] },
+        ]);
+    }
+
+    #[test]
+    fn test_modes() {
+        // "äö" is four bytes but two characters
+        let stream = "aäöa\r\nbc";
+        let bytes = find_markers(stream.as_bytes(), &[2], &MarkerOptions::default()).unwrap();
+        assert_eq!(bytes[0].offsets, vec![2, 3, 4, 5, 6]);
+
+        let options = MarkerOptions { mode: Mode::Unicode, ignore_whitespace: false };
+        let chars = find_markers(stream.as_bytes(), &[3], &options).unwrap();
+        assert_eq!(chars[0].offsets, vec![3, 4]);
+
+        let options = MarkerOptions { mode: Mode::Unicode, ignore_whitespace: true };
+        let chars = find_markers(stream.as_bytes(), &[3], &options).unwrap();
+        assert_eq!(chars[0].offsets, vec![3, 4, 7, 8]);
+
+        assert!(find_markers(&[0x61, 0xff][..], &[2], &options).is_err());
+        assert_eq!(naive_first_marker(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14), Some(19));
+        // Truncated four byte sequence
+        assert!(find_markers("a\u{1f600}".as_bytes()[..3].as_ref(), &[2], &options).is_err());
+    }
+
+    #[test]
+    fn test_parse_sizes() {
+        let args = |list: &str| vec!["--size".to_string(), list.to_string()];
+        assert_eq!(parse_sizes(&[]), Ok(vec![4, 14]));
+        assert_eq!(parse_sizes(&args("3,5")), Ok(vec![3, 5]));
+        assert_eq!(parse_sizes(&args("4,0")), Err("Marker length must be at least 1".to_string()));
+        assert_eq!(parse_sizes(&args("x")), Err("Invalid marker length \"x\"".to_string()));
+    }
+}
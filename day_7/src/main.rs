@@ -1,109 +1,443 @@
-use std::env;
-use std::collections::{HashMap, HashSet};
-use std::cell::RefCell;
-use std::fmt::Debug;
+use std::{env, fmt, fs, io};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 
 use log::debug;
+use serde::Serialize;
 
 use utils::{read_input, set_logging_level};
 
-#[derive(Eq, Hash, PartialEq)]
-struct File {
+/// Index of a directory in the `FileSystem` arena
+type DirId = usize;
+
+#[derive(Debug)]
+struct Directory {
     name: String,
-    size: usize
+    parent: Option<DirId>,
+    children: BTreeMap<String, DirId>,
+    /// File sizes by name
+    files: BTreeMap<String, usize>
+}
+
+/// Transcript line that cannot be processed, `line` is the line number
+#[derive(Debug, PartialEq, Eq)]
+struct TranscriptError {
+    line: usize,
+    text: String,
+    reason: String
 }
 
-impl Debug for File {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{} {}", self.size, self.name))
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {} ({:?})", self.line, self.reason, self.text)
     }
 }
 
+/// Splits a path to its parent path and last name, "a/b/c" -> ("a/b", "c")
+fn split_parent(path: &str) -> Result<(&str, &str), String> {
+    let trimmed = path.trim_end_matches('/');
+    let (parent, name) = match trimmed.rfind('/') {
+        Some(0) => ("/", &trimmed[1..]),
+        Some(index) => (&trimmed[..index], &trimmed[index + 1..]),
+        None => ("", trimmed)
+    };
+    match name {
+        "" | "." | ".." => Err(format!("invalid name in path {path:?}")),
+        _ => Ok((parent, name))
+    }
+}
+
+/// Directory tree stored in an arena, directories refer to each other by index
 #[derive(Debug)]
 struct FileSystem {
-    location: String,
-    nodes: HashMap<String, RefCell<HashSet<File>>>
+    location: DirId,
+    dirs: Vec<Directory>,
+    /// Is the previous command `ls`, i.e. are output lines expected
+    listing: bool
 }
 
 impl FileSystem {
+    const ROOT: DirId = 0;
+
     pub fn init_root() -> FileSystem{
-        let mut fs = FileSystem {
-            location: "/".to_string(),
-            nodes: HashMap::new()
-        };
-        fs.mkdir(&"/");
-        return fs;
-    }
-
-    fn ch(&mut self, dir: &str) {
-        match dir {
-            "/" => self.location = "/".to_string(),
-            ".." => {
-                let split: Vec<&str> = self.location[1..].split("/").collect();
-                let mut new_location = "/".to_string();
-                new_location.push_str(&split[..split.len() - 2].join("/"));
-                if new_location != "/" {
-                    new_location.push('/');
+        FileSystem {
+            location: FileSystem::ROOT,
+            dirs: vec![Directory {
+                name: String::new(),
+                parent: None,
+                children: BTreeMap::new(),
+                files: BTreeMap::new()
+            }],
+            listing: false
+        }
+    }
+
+    pub fn from_transcript(input: &str) -> Result<FileSystem, TranscriptError> {
+        let mut filesystem = FileSystem::init_root();
+        for (index, line) in input.lines().enumerate() {
+            filesystem.process_line(line).map_err(|reason| TranscriptError {
+                line: index + 1,
+                text: line.to_string(),
+                reason
+            })?;
+        }
+        Ok(filesystem)
+    }
+
+    /// Follows an absolute or relative path, missing directories are created
+    /// if `create` is set
+    fn walk(&mut self, path: &str, create: bool) -> Result<DirId, String> {
+        let mut current = if path.starts_with('/') { FileSystem::ROOT } else { self.location };
+        for name in path.split('/').filter(|name| !name.is_empty() && *name != ".") {
+            current = match name {
+                ".." => self.dirs[current].parent.unwrap_or(FileSystem::ROOT),
+                _ if create => self.mkdir(current, name)?,
+                _ => match self.dirs[current].children.get(name) {
+                    Some(child) => *child,
+                    None => return Err(format!("no such directory {name:?} in {}", self.path(current)))
                 }
-                self.location = new_location;
-            },
-            &_ => {
-                let mut new_location = self.location.to_owned();
-                new_location.push_str(&format!("{dir}/"));
-                self.mkdir(&new_location);
-                self.location = new_location;
+            };
+        }
+        Ok(current)
+    }
+
+    fn ch(&mut self, path: &str) -> Result<(), String> {
+        self.location = self.walk(path, true)?;
+        Ok(())
+    }
+
+    /// Is `dir` the same as or inside `ancestor`
+    fn is_within(&self, dir: DirId, ancestor: DirId) -> bool {
+        let mut current = Some(dir);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
             }
+            current = self.dirs[id].parent;
+        }
+        false
+    }
+
+    fn rm(&mut self, path: &str) -> Result<(), String> {
+        let (parent_path, name) = split_parent(path)?;
+        let parent = self.walk(parent_path, false)?;
+        if self.dirs[parent].files.remove(name).is_some() {
+            return Ok(());
+        }
+        let dir = match self.dirs[parent].children.get(name) {
+            Some(dir) => *dir,
+            None => return Err(format!("no such file or directory {path:?}"))
+        };
+        if self.is_within(self.location, dir) {
+            return Err(format!("cannot remove {path:?}, it contains the current directory"));
+        }
+        self.dirs[parent].children.remove(name);
+        self.dirs[dir].parent = None;
+        Ok(())
+    }
+
+    fn mv(&mut self, source: &str, target: &str) -> Result<(), String> {
+        let (source_parent_path, source_name) = split_parent(source)?;
+        let source_parent = self.walk(source_parent_path, false)?;
+        // Into an existing directory with the same name, otherwise to the given path
+        let (target_parent, target_name) = match self.walk(target, false) {
+            Ok(dir) => (dir, source_name.to_string()),
+            Err(_) => {
+                let (parent_path, name) = split_parent(target)?;
+                (self.walk(parent_path, false)?, name.to_string())
+            }
+        };
+        if self.dirs[target_parent].children.contains_key(&target_name) {
+            return Err(format!("directory {target_name:?} already exists in {}", self.path(target_parent)));
         }
+
+        if let Some(size) = self.dirs[source_parent].files.remove(source_name) {
+            self.dirs[target_parent].files.insert(target_name, size);
+            return Ok(());
+        }
+        let dir = match self.dirs[source_parent].children.get(source_name) {
+            Some(dir) => *dir,
+            None => return Err(format!("no such file or directory {source:?}"))
+        };
+        if self.is_within(target_parent, dir) {
+            return Err(format!("cannot move {source:?} inside itself"));
+        }
+        if self.dirs[target_parent].files.contains_key(&target_name) {
+            return Err(format!("file {target_name:?} already exists in {}", self.path(target_parent)));
+        }
+        self.dirs[source_parent].children.remove(source_name);
+        self.dirs[dir].name = target_name.clone();
+        self.dirs[dir].parent = Some(target_parent);
+        self.dirs[target_parent].children.insert(target_name, dir);
+        Ok(())
     }
 
-    fn mkdir(&mut self, dir: &str) {
-        self.nodes.entry(dir.to_string()).or_insert(RefCell::new(HashSet::new()));
+    fn touch(&mut self, path: &str) -> Result<(), String> {
+        let (parent_path, name) = split_parent(path)?;
+        let parent = self.walk(parent_path, false)?;
+        if self.dirs[parent].children.contains_key(name) {
+            return Err(format!("{path:?} is a directory"));
+        }
+        self.dirs[parent].files.entry(name.to_string()).or_insert(0);
+        Ok(())
     }
 
-    fn new_file(&mut self, name: &str, size: usize) {
-        self.nodes[&self.location].borrow_mut().insert(File {name: name.to_string(), size: size});
+    /// Child directory of `parent`, created if it doesn't exist
+    fn mkdir(&mut self, parent: DirId, name: &str) -> Result<DirId, String> {
+        if let Some(child) = self.dirs[parent].children.get(name) {
+            return Ok(*child);
+        }
+        if self.dirs[parent].files.contains_key(name) {
+            return Err(format!("{name:?} is a file in {}", self.path(parent)));
+        }
+        let child = self.dirs.len();
+        self.dirs.push(Directory {
+            name: name.to_string(),
+            parent: Some(parent),
+            children: BTreeMap::new(),
+            files: BTreeMap::new()
+        });
+        self.dirs[parent].children.insert(name.to_string(), child);
+        Ok(child)
     }
 
-    pub fn process_line(&mut self, line: &str) {
-        if line.starts_with("$ cd") {
-            self.ch(line.split(" ").last().unwrap())
-        } else if line.starts_with("$ ls") {
-            return;
-        } else if line.starts_with("dir") {
-            let dir: &str = line.split(" ").last().unwrap();
-            let mut new_location = self.location.to_owned();
-            new_location.push_str(&format!("{dir}/"));
-            self.mkdir(&new_location)
+    /// Adds a file to the current directory, a repeated entry replaces the earlier one
+    fn new_file(&mut self, name: &str, size: usize) -> Result<(), String> {
+        if self.dirs[self.location].children.contains_key(name) {
+            return Err(format!("{name:?} is a directory"));
+        }
+        self.dirs[self.location].files.insert(name.to_string(), size);
+        Ok(())
+    }
+
+    pub fn process_line(&mut self, line: &str) -> Result<(), String> {
+        if let Some(command) = line.strip_prefix("$ ") {
+            let command: Vec<&str> = command.split_whitespace().collect();
+            self.listing = false;
+            match command[..] {
+                ["cd", path] => self.ch(path),
+                ["ls"] => {
+                    // The new listing replaces the files seen before
+                    self.dirs[self.location].files.clear();
+                    self.listing = true;
+                    Ok(())
+                },
+                ["rm", path] => self.rm(path),
+                ["mv", source, target] => self.mv(source, target),
+                ["mkdir", path] => self.walk(path, true).map(|_| ()),
+                ["touch", path] => self.touch(path),
+                _ => Err("unknown command".to_string())
+            }
+        } else if !self.listing {
+            Err("output line without `$ ls`".to_string())
         } else {
-            let split: Vec<&str> = line.split(" ").collect();
-            let name: &str = split[1];
-            let size: usize = split[0].parse().unwrap();
-            self.new_file(name, size)
+            let split: Vec<&str> = line.split_whitespace().collect();
+            match split[..] {
+                ["dir", name] if split_parent(name) == Ok(("", name)) => {
+                    self.mkdir(self.location, name).map(|_| ())
+                },
+                [size, name] if split_parent(name) == Ok(("", name)) => match size.parse::<usize>() {
+                    Ok(size) => self.new_file(name, size),
+                    Err(_) => Err(format!("invalid file size {size:?}"))
+                },
+                _ => Err("expected \"dir NAME\" or \"SIZE NAME\"".to_string())
+            }
+        }
+    }
+
+    /// Absolute path of the directory, ends with a slash
+    pub fn path(&self, dir: DirId) -> String {
+        let mut names: Vec<&str> = Vec::new();
+        let mut current = dir;
+        while let Some(parent) = self.dirs[current].parent {
+            names.push(&self.dirs[current].name);
+            current = parent;
         }
+        let mut path = "/".to_string();
+        for name in names.iter().rev() {
+            path.push_str(&format!("{name}/"));
+        }
+        path
     }
 
-    fn immediate_size(&self, dir: &str) -> usize {
-        let mut size: usize = 0;
+    /// Directories reachable from the root, every parent before its children
+    fn pre_order(&self) -> Vec<DirId> {
+        let mut order: Vec<DirId> = Vec::new();
+        let mut stack: Vec<DirId> = vec![FileSystem::ROOT];
+        while let Some(dir) = stack.pop() {
+            order.push(dir);
+            stack.extend(self.dirs[dir].children.values().rev());
+        }
+        order
+    }
 
-        for file in self.nodes[dir].borrow().iter() {
-            size += file.size;
+    /// Total size of every directory, indexed by `DirId`, computed children first
+    pub fn dir_sizes(&self) -> Vec<usize> {
+        let mut sizes: Vec<usize> = self.dirs.iter().map(|dir| dir.files.values().sum()).collect();
+        for dir in self.pre_order().into_iter().rev() {
+            if let Some(parent) = self.dirs[dir].parent {
+                sizes[parent] += sizes[dir];
+            }
         }
-        return size;
+        sizes
     }
 
     pub fn folder_sizes(&self) -> HashMap<String, usize> {
-        let mut size_map: HashMap<String, usize> = HashMap::new();
-        for dir in self.nodes.keys() {
-            let mut size: usize = 0;
-            for child in self.nodes.keys() {
-                if child.starts_with(dir) {
-                    size += self.immediate_size(child);
+        let sizes = self.dir_sizes();
+        self.pre_order()
+            .into_iter()
+            .map(|dir| (self.path(dir), sizes[dir]))
+            .collect()
+    }
+}
+
+/// Directory of the JSON export, sizes include subdirectories
+#[derive(Debug, Serialize)]
+struct DirectoryReport {
+    name: String,
+    size: usize,
+    files: BTreeMap<String, usize>,
+    directories: Vec<DirectoryReport>
+}
+
+impl FileSystem {
+    /// Number of directories between `dir` and the root
+    fn depth(&self, dir: DirId) -> usize {
+        let mut depth: usize = 0;
+        let mut current = dir;
+        while let Some(parent) = self.dirs[current].parent {
+            depth += 1;
+            current = parent;
+        }
+        depth
+    }
+
+    /// `tree`-style render with sizes, directories deeper than `max_depth` are left out
+    pub fn tree(&self, max_depth: Option<usize>) -> String {
+        let sizes = self.dir_sizes();
+        let mut lines: Vec<String> = vec![format!("/ ({})", sizes[FileSystem::ROOT])];
+        self.tree_lines(FileSystem::ROOT, "", 1, max_depth, &sizes, &mut lines);
+        lines.join("\n")
+    }
+
+    fn tree_lines(
+        &self, dir: DirId, prefix: &str, depth: usize, max_depth: Option<usize>, sizes: &[usize], lines: &mut Vec<String>
+    ) {
+        if max_depth.is_some_and(|max| depth > max) {
+            return;
+        }
+        // Directories and files sorted by name like `tree`
+        let mut entries: Vec<(&str, Option<DirId>, usize)> = self.dirs[dir].children.iter()
+            .map(|(name, child)| (name.as_str(), Some(*child), sizes[*child]))
+            .chain(self.dirs[dir].files.iter().map(|(name, size)| (name.as_str(), None, *size)))
+            .collect();
+        entries.sort();
+
+        for (index, (name, child, size)) in entries.iter().enumerate() {
+            let last = index + 1 == entries.len();
+            let branch = if last { "└── " } else { "├── " };
+            match child {
+                Some(child) => {
+                    lines.push(format!("{prefix}{branch}{name}/ ({size})"));
+                    let next_prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+                    self.tree_lines(*child, &next_prefix, depth + 1, max_depth, sizes, lines);
+                },
+                None => lines.push(format!("{prefix}{branch}{name} {size}"))
+            }
+        }
+    }
+
+    /// `du`-style listing of directory paths and sizes, largest first
+    pub fn du(&self, max_depth: Option<usize>) -> Vec<(String, usize)> {
+        let sizes = self.dir_sizes();
+        let mut listing: Vec<(String, usize)> = self.pre_order()
+            .into_iter()
+            .filter(|dir| max_depth.is_none_or(|max| self.depth(*dir) <= max))
+            .map(|dir| (self.path(dir), sizes[dir]))
+            .collect();
+        listing.sort_by(|(path1, size1), (path2, size2)| size2.cmp(size1).then(path1.cmp(path2)));
+        listing
+    }
+
+    pub fn report(&self) -> DirectoryReport {
+        self.dir_report(FileSystem::ROOT, &self.dir_sizes())
+    }
+
+    fn dir_report(&self, dir: DirId, sizes: &[usize]) -> DirectoryReport {
+        DirectoryReport {
+            name: self.dirs[dir].name.clone(),
+            size: sizes[dir],
+            files: self.dirs[dir].files.clone(),
+            directories: self.dirs[dir].children.values().map(|child| self.dir_report(*child, sizes)).collect()
+        }
+    }
+}
+
+impl FileSystem {
+    /// Walks a local directory, entries whose names can't be written to a
+    /// transcript (non UTF-8 or whitespace) and symbolic links are skipped
+    pub fn from_directory(root: &Path) -> io::Result<FileSystem> {
+        let mut filesystem = FileSystem::init_root();
+        filesystem.read_directory(FileSystem::ROOT, root)?;
+        Ok(filesystem)
+    }
+
+    fn read_directory(&mut self, dir: DirId, path: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) if !name.contains(char::is_whitespace) => name,
+                _ => {
+                    debug!("Skipping {:?}", entry.path());
+                    continue;
                 }
+            };
+            if file_type.is_dir() {
+                let child = self.mkdir(dir, &name).map_err(io::Error::other)?;
+                self.read_directory(child, &entry.path())?;
+            } else if file_type.is_file() {
+                self.dirs[dir].files.insert(name, entry.metadata()?.len() as usize);
+            } else {
+                debug!("Skipping {:?}", entry.path());
             }
-            size_map.insert(dir.to_string(), size);
         }
-        return size_map;
+        Ok(())
+    }
+
+    /// `$ cd`/`$ ls` transcript that visits every directory once
+    pub fn transcript(&self) -> String {
+        let mut lines: Vec<String> = vec!["$ cd /".to_string()];
+        self.transcript_lines(FileSystem::ROOT, &mut lines);
+        lines.join("\n")
+    }
+
+    fn transcript_lines(&self, dir: DirId, lines: &mut Vec<String>) {
+        lines.push("$ ls".to_string());
+        for name in self.dirs[dir].children.keys() {
+            lines.push(format!("dir {name}"));
+        }
+        for (name, size) in &self.dirs[dir].files {
+            lines.push(format!("{size} {name}"));
+        }
+        for (name, child) in &self.dirs[dir].children {
+            lines.push(format!("$ cd {name}"));
+            self.transcript_lines(*child, lines);
+            lines.push("$ cd ..".to_string());
+        }
+    }
+}
+
+/// Builds a transcript from a local directory and checks that parsing it gives the same sizes
+fn transcript_from_directory(root: &Path) -> io::Result<String> {
+    let walked = FileSystem::from_directory(root)?;
+    let transcript = walked.transcript();
+    let parsed = FileSystem::from_transcript(&transcript)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    if parsed.folder_sizes() != walked.folder_sizes() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Transcript sizes differ from the directory"));
     }
+    Ok(transcript)
 }
 
 fn size_of_small_folder(size_map: &HashMap<String, usize>, max: &usize) -> usize {
@@ -132,15 +466,45 @@ fn smallest_possible_folder(size_map: &HashMap<String, usize>, min: &usize) -> (
 fn main() {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
-    let input = read_input(&args);
-    let mut filesystem = FileSystem::init_root();
-
-    for line in input.lines() {
-        filesystem.process_line(line);
+    let input = match args.iter().position(|r| r == "--from-dir") {
+        Some(pos) => match transcript_from_directory(Path::new(&args[pos + 1])) {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        },
+        None => read_input(&args)
+    };
+    if args.contains(&String::from("--transcript")) {
+        println!("{input}");
+        return;
     }
+    let filesystem = match FileSystem::from_transcript(&input) {
+        Ok(filesystem) => filesystem,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
     debug!("{:?}", filesystem);
-    debug!("{:?}", filesystem.nodes.keys());
+    let max_depth: Option<usize> = args.iter()
+        .position(|r| r == "--depth")
+        .map(|pos| args[pos + 1].parse().unwrap());
+    if args.contains(&String::from("--tree")) {
+        println!("{}", filesystem.tree(max_depth));
+    }
+    if args.contains(&String::from("--du")) {
+        for (path, size) in filesystem.du(max_depth) {
+            println!("{size:>10}  {path}");
+        }
+    }
+    if args.contains(&String::from("--json")) {
+        println!("{}", serde_json::to_string_pretty(&filesystem.report()).unwrap());
+        return;
+    }
+    debug!("{:?}", filesystem.pre_order().iter().map(|dir| filesystem.path(*dir)).collect::<Vec<String>>());
     let size_map = filesystem.folder_sizes();
     debug!("{:?}", size_map);
     let size_of_small = size_of_small_folder(&size_map, &100000);
@@ -148,10 +512,125 @@ fn main() {
 
     let total_disk_space: usize = 70000000;
     let required_disk_space: usize = 30000000;
-    let free_disk_space: usize = total_disk_space - size_map["/"];
-    let min_deletion: usize = required_disk_space - free_disk_space;
+    let free_disk_space: usize = total_disk_space.saturating_sub(size_map["/"]);
+    let min_deletion: usize = required_disk_space.saturating_sub(free_disk_space);
     debug!("Free disk space: {free_disk_space}");
     debug!("Minimum folder size: {min_deletion}");
     let (smallest_dir, smallest_size) = smallest_possible_folder(&size_map, &min_deletion);
     println!("Smallest applicable directory: {smallest_dir} ({smallest_size})");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_transcript(input: &str) -> FileSystem {
+        FileSystem::from_transcript(input).unwrap()
+    }
+
+    #[test]
+    fn test_folder_sizes() {
+        let filesystem = from_transcript(&read_input(&vec!["--test".to_string()]));
+        let size_map = filesystem.folder_sizes();
+        assert_eq!(size_map["/"], 48381165);
+        assert_eq!(size_map["/a/e/"], 584);
+        assert_eq!(size_map["/d/"], 24933642);
+        assert_eq!(size_of_small_folder(&size_map, &100000), 95437);
+        assert_eq!(smallest_possible_folder(&size_map, &8381165), ("/d/".to_string(), 24933642));
+    }
+
+    #[test]
+    fn test_prefix_sibling() {
+        let filesystem = from_transcript("\
+            $ cd /\n$ ls\ndir a\ndir ab\n\
+            $ cd a\n$ ls\n10 x\n$ cd ..\n\
+            $ cd ab\n$ ls\n20 y\n$ cd ..\n$ cd ..");
+        let size_map = filesystem.folder_sizes();
+        assert_eq!(size_map["/a/"], 10);
+        assert_eq!(size_map["/ab/"], 20);
+        assert_eq!(size_map["/"], 30);
+    }
+
+    #[test]
+    fn test_extended_commands() {
+        let mut filesystem = from_transcript("\
+            $ mkdir /a/b\n$ cd /a/b\n$ ls\n5 x\n5 x\n$ ls\n5 z\n\
+            $ cd ../..\n$ touch a/empty\n$ mkdir c\n$ mv a/b c\n$ mv a/empty c/b/renamed\n\
+            $ cd c/b\n$ ls\n7 y\n0 renamed\n$ cd /\n$ mkdir d\n$ rm d");
+        let size_map = filesystem.folder_sizes();
+        // Listing again replaces the files, repeated entries aren't counted twice
+        assert_eq!(size_map["/c/b/"], 7);
+        assert_eq!(size_map["/a/"], 0);
+        assert!(!size_map.contains_key("/a/b/"));
+        assert!(!size_map.contains_key("/d/"));
+        let dir = filesystem.walk("/c/b", false).unwrap();
+        assert_eq!(filesystem.dirs[dir].files.keys().collect::<Vec<&String>>(), vec!["renamed", "y"]);
+    }
+
+    #[test]
+    fn test_transcript_errors() {
+        let error = |input: &str| FileSystem::from_transcript(input).unwrap_err();
+        assert_eq!(error("$ cd /\n123 a").reason, "output line without `$ ls`");
+        assert_eq!(error("$ ls\nabc a").reason, "invalid file size \"abc\"");
+        assert_eq!(error("$ ls\n1 a\n$ cp a b").line, 3);
+        assert_eq!(error("$ rm a").reason, "no such file or directory \"a\"");
+        assert_eq!(error("$ cd a\n$ rm /a").reason, "cannot remove \"/a\", it contains the current directory");
+        assert_eq!(error("$ mkdir a\n$ mv a a/b").reason, "cannot move \"a\" inside itself");
+    }
+
+    #[test]
+    fn test_reports() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let filesystem = from_transcript(&input);
+        let tree = filesystem.tree(Some(2));
+        assert_eq!(tree.lines().take(5).collect::<Vec<&str>>(), vec![
+            "/ (48381165)",
+            "├── a/ (94853)",
+            "│   ├── e/ (584)",
+            "│   ├── f 29116",
+            "│   ├── g 2557",
+        ]);
+        assert_eq!(filesystem.tree(None).lines().count(), 14);
+
+        let du = filesystem.du(Some(1));
+        assert_eq!(du, vec![
+            ("/".to_string(), 48381165),
+            ("/d/".to_string(), 24933642),
+            ("/a/".to_string(), 94853),
+        ]);
+
+        let json = serde_json::to_value(filesystem.report()).unwrap();
+        assert_eq!(json["size"], 48381165);
+        assert_eq!(json["files"]["b.txt"], 14848514);
+        assert_eq!(json["directories"][0]["directories"][0]["name"], "e");
+    }
+
+    #[test]
+    fn test_from_directory() {
+        let root = env::temp_dir().join(format!("day_7_test_{}", std::process::id()));
+        fs::create_dir_all(root.join("a/e")).unwrap();
+        fs::create_dir_all(root.join("d")).unwrap();
+        fs::write(root.join("b.txt"), [0; 140]).unwrap();
+        fs::write(root.join("a/f"), [0; 29]).unwrap();
+        fs::write(root.join("a/e/i"), [0; 5]).unwrap();
+        fs::write(root.join("d/with space"), [0; 7]).unwrap();
+
+        let transcript = transcript_from_directory(&root);
+        let walked = FileSystem::from_directory(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        let size_map = from_transcript(&transcript.unwrap()).folder_sizes();
+        assert_eq!(size_map, walked.unwrap().folder_sizes());
+        assert_eq!(size_map["/"], 174);
+        assert_eq!(size_map["/a/"], 34);
+        assert_eq!(size_map["/d/"], 0);
+    }
+
+    #[test]
+    fn test_transcript_round_trip() {
+        let input = read_input(&vec!["--test".to_string()]);
+        let filesystem = from_transcript(&input);
+        let parsed = from_transcript(&filesystem.transcript());
+        assert_eq!(parsed.folder_sizes(), filesystem.folder_sizes());
+    }
+}
@@ -1,33 +1,161 @@
-use std::env;
-use std::collections::HashSet;
+use std::{env, fs, io, time::Instant};
+use std::fmt::Display;
 
 use log::debug;
-use itertools::iproduct;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 use utils::{read_input, set_logging_level};
 
 #[derive(Debug)]
 struct Forest {
     trees: Vec<usize>,
-    width: usize
+    width: usize,
+    /// Number of rows, kept up to date by `add_row`
+    height: usize
 }
 
-#[derive(Debug, Eq, Hash, PartialEq)]
-struct Tree {
-    row: usize,
-    col: usize
+/// Direction a tree looks towards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Left,
+    Right,
+    Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight
+}
+
+impl Direction {
+    const AXES: [Direction; 4] = [Direction::Up, Direction::Left, Direction::Right, Direction::Down];
+    const ALL: [Direction; 8] = [
+        Direction::Up, Direction::Left, Direction::Right, Direction::Down,
+        Direction::UpLeft, Direction::UpRight, Direction::DownLeft, Direction::DownRight
+    ];
+
+    /// Row and column step towards the direction
+    fn delta(&self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+            Direction::Down => (1, 0),
+            Direction::UpLeft => (-1, -1),
+            Direction::UpRight => (-1, 1),
+            Direction::DownLeft => (1, -1),
+            Direction::DownRight => (1, 1)
+        }
+    }
+}
+
+/// Rules for how far trees can see, the defaults are the puzzle rules
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ViewOptions {
+    /// Look diagonally as well as along rows and columns
+    diagonals: bool,
+    /// Trees lower than the viewing tree's height plus `tolerance` are seen over
+    tolerance: usize,
+    /// Line of sight limit, trees further away than this can't see the edge
+    max_distance: Option<usize>
+}
+
+impl ViewOptions {
+    fn directions(&self) -> &'static [Direction] {
+        if self.diagonals { &Direction::ALL } else { &Direction::AXES }
+    }
+}
+
+/// Per-tree matrices in the same row-major order as `Forest::trees`
+#[derive(Debug)]
+struct Views {
+    directions: &'static [Direction],
+    /// Visible from outside the forest in at least one direction
+    visible: Vec<bool>,
+    /// Viewing distances of each tree in a row, ordered like `directions`.
+    /// They are bounded by the forest side, so `u32` keeps large forests small.
+    distances: Vec<u32>,
+    scenic: Vec<usize>
+}
+
+impl Views {
+    /// Viewing distance of the tree in each direction
+    pub fn distances(&self, index: usize) -> &[u32] {
+        let count = self.directions.len();
+        &self.distances[index * count..(index + 1) * count]
+    }
+
+    /// Directions and distances as "Up 2, Left 1, ..."
+    pub fn format_distances(&self, index: usize) -> String {
+        self.directions.iter()
+            .zip(self.distances(index))
+            .map(|(direction, distance)| format!("{direction:?} {distance}"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    pub fn visible_trees(&self) -> usize {
+        self.visible.iter().filter(|visible| **visible).count()
+    }
+
+    /// Index and score of the first tree with the highest scenic score
+    pub fn highest_scenic(&self) -> (usize, usize) {
+        self.scenic.iter()
+            .copied()
+            .enumerate()
+            .max_by_key(|(index, score)| (*score, std::cmp::Reverse(*index)))
+            .unwrap_or((0, 0))
+    }
+
+    /// Indices and scores of the `k` highest scoring trees
+    pub fn top(&self, k: usize) -> Vec<(usize, usize)> {
+        let mut ranked: Vec<(usize, usize)> = self.scenic.iter().copied().enumerate().collect();
+        ranked.sort_by_key(|(index, score)| (std::cmp::Reverse(*score), *index));
+        ranked.truncate(k);
+        ranked
+    }
+}
+
+/// Comma separated matrix with one row per line
+fn matrix_csv<T: Display>(values: &[T], width: usize) -> String {
+    values.chunks(width)
+        .map(|row| row.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(","))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Binary greyscale PGM, values are scaled so that the largest one is white.
+/// Scaling is done in `u128` since saturated scenic scores are `usize::MAX`.
+fn matrix_pgm(values: &[usize], width: usize) -> Vec<u8> {
+    let max = values.iter().copied().max().unwrap_or(0).max(1) as u128;
+    let mut image = format!("P5\n{} {}\n255\n", width, values.len() / width).into_bytes();
+    image.extend(values.iter().map(|value| (*value as u128 * 255 / max) as u8));
+    image
+}
+
+/// Writes the visibility mask and scenic scores as `<prefix>_visible` and
+/// `<prefix>_scenic` CSV and PGM files
+fn export(prefix: &str, views: &Views, width: usize) -> io::Result<()> {
+    let visible: Vec<usize> = views.visible.iter().map(|visible| *visible as usize).collect();
+    for (name, values) in [("visible", &visible), ("scenic", &views.scenic)] {
+        fs::write(format!("{prefix}_{name}.csv"), matrix_csv(values, width))?;
+        fs::write(format!("{prefix}_{name}.pgm"), matrix_pgm(values, width))?;
+        println!("Wrote {prefix}_{name}.csv and {prefix}_{name}.pgm");
+    }
+    Ok(())
 }
 
 impl Forest {
     pub fn new() -> Forest {
-        Forest {trees: Vec::new(), width: 0}
+        Forest {trees: Vec::new(), width: 0, height: 0}
     }
 
     pub fn add_row(&mut self, line: &str) {
-        let line_width = line.len() as usize;
+        let line_width = line.len();
         if self.width == 0 {
             self.width = line_width;
-        } else if (self.width > 0) & (self.width != line_width) {
+        } else if self.width != line_width {
             panic!("Mismatching line width");
         }
 
@@ -35,176 +163,233 @@ impl Forest {
             let n: usize = c.to_digit(10).unwrap() as usize;
             self.trees.push(n)
         }
+        self.height += 1;
+    }
+
+    /// Random forest of tree heights 0-9
+    pub fn generate(width: usize, height: usize, seed: u64) -> Forest {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Forest {
+            trees: (0..width * height).map(|_| rng.gen_range(0..10)).collect(),
+            width,
+            height
+        }
+    }
+
+    /// Forest rising towards the bottom right corner, every tree sees over
+    /// everything up and left of it
+    pub fn slope(size: usize) -> Forest {
+        Forest {
+            trees: (0..size * size).map(|i| i / size + i % size).collect(),
+            width: size,
+            height: size
+        }
     }
 
     pub fn tree_loc(&self, row: &usize, col: &usize) -> &usize {
         // https://stackoverflow.com/a/2151141/14536215
         // https://en.wikipedia.org/wiki/Row-_and_column-major_order
-        &self.trees[(self.width * row + col)]
+        &self.trees[self.width * row + col]
     }
 
     pub fn height(&self) ->usize {
-        self.trees.len() / self.width
+        self.height
     }
 
-    fn visible_trees_row(&self, row: &usize) -> HashSet<Tree> {
-        let mut visible_trees: HashSet<Tree> = HashSet::new();
-        let mut tree_i: &usize;
-        // Forwards
-        let mut highest_tree: &usize = self.tree_loc(row, &0);
-        visible_trees.insert(Tree {row: *row, col: 0});
-        for col in 1..self.width {
-            tree_i = self.tree_loc(row, &col);
-
-            if tree_i > highest_tree {
-                visible_trees.insert(Tree {row: *row, col: col});
-                highest_tree = tree_i;
-            }
+    /// Neighbouring position, None if it is outside the forest
+    fn step(&self, row: usize, col: usize, (d_row, d_col): (isize, isize)) -> Option<(usize, usize)> {
+        let row = row.checked_add_signed(d_row).filter(|row| *row < self.height())?;
+        let col = col.checked_add_signed(d_col).filter(|col| *col < self.width)?;
+        Some((row, col))
+    }
 
-            if highest_tree == &9 {
-                break;
-            }
+    /// Lines of trees as (first index, index step, length), each starting
+    /// from the edge the trees look towards. The first trees are on the edge
+    /// row and column the direction points to, the length is the number of
+    /// steps back before leaving the forest.
+    fn lines(&self, direction: Direction) -> Vec<(usize, isize, usize)> {
+        if self.trees.is_empty() {
+            return Vec::new();
         }
-        // Backwards
-        let mut highest_tree: &usize = self.tree_loc(row, &(self.width - 1));
-        visible_trees.insert(Tree {row: *row, col: (self.width - 1)});
-        for col in (0..self.width - 1).rev() {
-            tree_i = self.tree_loc(&row, &col);
-
-            if tree_i > highest_tree {
-                visible_trees.insert(Tree {row: *row, col: col});
-                highest_tree = tree_i;
-            }
-
-            if highest_tree == &9 {
-                break;
-            }
+        let (d_row, d_col) = direction.delta();
+        let (width, height) = (self.width, self.height);
+        let offset = -(d_row * width as isize + d_col);
+        // Trees in the line along one axis, the other axis doesn't limit it
+        let along = |d: isize, position: usize, size: usize| match d {
+            1 => position + 1,
+            -1 => size - position,
+            _ => usize::MAX
+        };
+        let line = |row: usize, col: usize| {
+            let length = along(d_row, row, height).min(along(d_col, col, width));
+            (width * row + col, offset, length)
+        };
+        let edge_row = if d_row == 1 { height - 1 } else { 0 };
+        let edge_col = if d_col == 1 { width - 1 } else { 0 };
+        let mut lines: Vec<(usize, isize, usize)> = Vec::with_capacity(width + height);
+        if d_row != 0 {
+            lines.extend((0..width).map(|col| line(edge_row, col)));
         }
-        debug!("row {} visible: {:?}", row, visible_trees);
-        return visible_trees;
-    }
-
-    fn visible_trees_col(&self, col: &usize) -> HashSet<Tree> {
-        let mut visible_trees: HashSet<Tree> = HashSet::new();
-        let mut tree_i: &usize;
-        // Forwards
-        let mut highest_tree: &usize = self.tree_loc(&0, col);
-        visible_trees.insert(Tree {row: 0, col: *col});
-        for row in 1..self.height() {
-            tree_i = self.tree_loc(&row, col);
-
-            if tree_i > highest_tree {
-                visible_trees.insert(Tree {row: row, col: *col});
-                highest_tree = tree_i;
-            }
-
-            if highest_tree == &9 {
-                break;
-            }
+        if d_col != 0 {
+            lines.extend((0..height).filter(|row| d_row == 0 || *row != edge_row).map(|row| line(row, edge_col)));
         }
-        // Backwards
-        let mut highest_tree: &usize = self.tree_loc(&(self.height() - 1), col);
-        visible_trees.insert(Tree {row: (self.height() - 1), col: *col});
-        for row in (0..self.height() - 1).rev() {
-            tree_i = self.tree_loc(&row, col);
-
-            if tree_i > highest_tree {
-                visible_trees.insert(Tree {row: row, col: *col});
-                highest_tree = tree_i;
-            }
+        lines
+    }
 
-            if highest_tree == &9 {
-                break;
+    /// Sweeps every line in one direction with a monotonic stack of the trees
+    /// that are not yet blocked, each tree is pushed and popped once. The stack
+    /// heights never increase towards the top, so with a tolerance the nearest
+    /// blocker is found with a binary search.
+    ///
+    /// Without a tolerance the tree that pops another one is the nearest tree
+    /// at least as high on its other side, so the `reverse` direction is
+    /// filled in by the same sweep.
+    fn sweep(&self, d: usize, reverse: Option<usize>, options: &ViewOptions, visible: &mut [bool], distances: &mut [u32]) {
+        let count = options.directions().len();
+        let max_distance = options.max_distance.unwrap_or(usize::MAX);
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+        for (first, offset, length) in self.lines(options.directions()[d]) {
+            let index_at = |position: usize| first.wrapping_add_signed(offset * position as isize);
+            stack.clear();
+            for position in 0..length {
+                let index = index_at(position);
+                let height = self.trees[index];
+                // Trees lower than this one can't block anything behind it,
+                // when filling the reverse direction neither can one as high
+                let mut level = None;
+                while let Some(&(behind, top)) = stack.last() {
+                    if top > height || (top == height && reverse.is_none()) {
+                        break;
+                    }
+                    stack.pop();
+                    if let Some(r) = reverse {
+                        distances[index_at(behind) * count + r] = (position - behind).min(max_distance) as u32;
+                    }
+                    if top == height {
+                        level = Some(behind);
+                        break;
+                    }
+                }
+                let blocking = height + options.tolerance;
+                let blocker = match options.tolerance {
+                    0 => level.or(stack.last().map(|(behind, _)| *behind)),
+                    _ => stack.partition_point(|(_, top)| *top >= blocking).checked_sub(1).map(|i| stack[i].0)
+                };
+                let distance = match blocker {
+                    Some(blocker) => position - blocker,
+                    None => {
+                        visible[index] |= position <= max_distance;
+                        position
+                    }
+                };
+                distances[index * count + d] = distance.min(max_distance) as u32;
+                stack.push((position, height));
+            }
+            // Trees left on the stack see all the way to the far edge
+            if let Some(r) = reverse {
+                for (behind, _) in stack.drain(..) {
+                    let distance = length - 1 - behind;
+                    let index = index_at(behind);
+                    visible[index] |= distance <= max_distance;
+                    distances[index * count + r] = distance.min(max_distance) as u32;
+                }
             }
         }
-        debug!("col {} visible: {:?}", col, visible_trees);
-        return visible_trees;
     }
 
-    pub fn visible_trees(&self) -> usize {
-        let mut visible_trees: HashSet<Tree> = HashSet::new();
-        for row in 0..self.height() {
-            visible_trees.extend(self.visible_trees_row(&row));
-        }
-        for col in 0..self.width {
-            visible_trees.extend(self.visible_trees_col(&col));
+    pub fn views(&self, options: &ViewOptions) -> Views {
+        let directions = options.directions();
+        let mut visible: Vec<bool> = vec![false; self.trees.len()];
+        let mut distances: Vec<u32> = vec![0; self.trees.len() * directions.len()];
+        for (d, direction) in directions.iter().enumerate() {
+            let (d_row, d_col) = direction.delta();
+            let reverse = match options.tolerance {
+                0 => directions.iter().position(|other| other.delta() == (-d_row, -d_col)),
+                _ => None
+            };
+            // Already filled in by the sweep the other way
+            if reverse.is_some_and(|r| r < d) {
+                continue;
+            }
+            self.sweep(d, reverse, options, &mut visible, &mut distances);
         }
-        return visible_trees.len();
+        // Eight long viewing distances can overflow on large forests
+        let scenic: Vec<usize> = distances.chunks(directions.len())
+            .map(|d| d.iter().fold(1, |score: usize, distance| score.saturating_mul(*distance as usize)))
+            .collect();
+        Views { directions, visible, distances, scenic }
     }
 
-    fn scenic_score(&self, row: &usize, col: &usize) -> usize {
-        let tree_height: &usize = self.tree_loc(row, col);
+    /// Walks outward from a single tree, used as the baseline in tests and
+    /// benchmarks. Returns visibility and the scenic score.
+    fn naive_view(&self, row: usize, col: usize, options: &ViewOptions) -> (bool, usize) {
+        let blocking: usize = self.tree_loc(&row, &col) + options.tolerance;
+        let max_distance = options.max_distance.unwrap_or(usize::MAX);
+        let mut visible = false;
         let mut scenic_score: usize = 1;
-        let mut tree_i: &usize;
-
-        // Up
-        for (e, irow) in (0..*row).rev().enumerate() {
-            tree_i = self.tree_loc(&irow, col);
-
-            if (tree_i >= tree_height) | (irow == 0) {
-                let direction_score = e + 1;
-                debug!("up: {direction_score}");
-                scenic_score *= direction_score;
-                break;
-            }
-        }
-
-        // Left
-        for (e, icol) in (0..*col).rev().enumerate() {
-            tree_i = self.tree_loc(row, &icol);
-
-            if (tree_i >= tree_height) | (icol == 0) {
-                let direction_score = e + 1;
-                debug!("left: {direction_score}");
-                scenic_score *= direction_score;
-                break;
+        for direction in options.directions() {
+            let mut distance: usize = 0;
+            let mut blocked = false;
+            let mut position = (row, col);
+            while let Some(next) = self.step(position.0, position.1, direction.delta()) {
+                distance += 1;
+                position = next;
+                if *self.tree_loc(&next.0, &next.1) >= blocking {
+                    blocked = true;
+                    break;
+                }
             }
+            visible |= !blocked && distance <= max_distance;
+            scenic_score = scenic_score.saturating_mul(distance.min(max_distance));
         }
+        debug!("row: {} col: {} visible: {} score: {}", row, col, visible, scenic_score);
+        (visible, scenic_score)
+    }
+}
 
-        // Right
-        for (e, icol) in ((*col + 1)..self.width).enumerate() {
-            tree_i = self.tree_loc(row, &icol);
-            debug!("{e}");
-            if (tree_i >= tree_height) | (icol + 1 == self.width){
-                let direction_score = e + 1;
-                debug!("right: {direction_score}");
-                scenic_score *= direction_score;
-                break;
-            }
-        }
-
-        // Down
-        for (e, irow) in ((*row + 1)..self.height()).enumerate() {
-            tree_i = self.tree_loc(&irow, col);
-
-            if (tree_i >= tree_height) | (irow + 1 == self.height()) {
-                let direction_score = e + 1;
-                debug!("down: {direction_score}");
-                scenic_score *= direction_score;
-                break;
-            }
-        }
-        debug!("row: {} col: {} score: {}", row, col, scenic_score);
-        return scenic_score;
+/// Largest sloped forest the tree by tree walk is run on
+const NAIVE_LIMIT: usize = 1000;
+
+fn time_forest(name: &str, forest: &Forest, naive: bool) {
+    let start = Instant::now();
+    let views = forest.views(&ViewOptions::default());
+    let visible = views.visible_trees();
+    let (_, highest) = views.highest_scenic();
+    println!("{name}, stack sweeps: {visible} visible, highest score {highest} in {:?}", start.elapsed());
+    if !naive {
+        println!("{name}, tree by tree walk skipped, over {NAIVE_LIMIT}x{NAIVE_LIMIT}");
+        return;
     }
 
-    pub fn highest_scenic(&self) -> usize {
-        let mut high_score: usize = 0;
-        for (row, col) in iproduct!(1..(self.width - 1), 1..(self.height() - 1)) {
-            let tree_score: usize = self.scenic_score(&row, &col);
-            if tree_score > high_score {
-                high_score = tree_score;
-            }
-        }
+    let start = Instant::now();
+    let naive_highest = (0..forest.height())
+        .flat_map(|row| (0..forest.width).map(move |col| (row, col)))
+        .map(|(row, col)| forest.naive_view(row, col, &ViewOptions::default()).1)
+        .max()
+        .unwrap();
+    println!("{name}, tree by tree walk: highest score {naive_highest} in {:?}", start.elapsed());
+}
 
-        return high_score;
-    }
+/// Times the stack sweeps against walking outward from every tree
+fn benchmark(size: usize) {
+    println!("Benchmark with generated {size}x{size} forests");
+    time_forest("Random", &Forest::generate(size, size, 0), true);
+    time_forest("Slope", &Forest::slope(size), size <= NAIVE_LIMIT);
 }
 
+fn parse_option(args: &[String], name: &str) -> Option<usize> {
+    args.iter().position(|r| r == name).map(|pos| args[pos + 1].parse().unwrap())
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
+
+    if let Some(pos) = args.iter().position(|r| r == "--bench") {
+        benchmark(args[pos + 1].parse().unwrap());
+        return;
+    }
+
     let input = read_input(&args);
     let mut forest = Forest::new();
 
@@ -213,13 +398,116 @@ fn main() {
     }
     debug!("{:?}", forest);
     debug!("N trees: {}", forest.trees.len());
-    debug!("(3, 4) {}", forest.tree_loc(&3, &4));
-    debug!("(4, 4) {}", forest.tree_loc(&4, &4));
     println!("Width: {}", forest.width);
     println!("Height: {}", forest.height());
-    let visible = forest.visible_trees();
+    let options = ViewOptions {
+        diagonals: args.contains(&String::from("--diagonals")),
+        tolerance: parse_option(&args, "--tolerance").unwrap_or(0),
+        max_distance: parse_option(&args, "--max-distance")
+    };
+    if options != ViewOptions::default() {
+        println!("View options: {options:?}");
+    }
+    let views = forest.views(&options);
+    let visible = views.visible_trees();
     println!("Visible trees: {visible}");
 
-    let scenic_score: usize = forest.highest_scenic();
-    println!("Highest scenic score: {scenic_score}")
+    let (best, scenic_score) = views.highest_scenic();
+    println!("Highest scenic score: {scenic_score}");
+    println!(
+        "At row {} col {}, viewing distances: {}",
+        best / forest.width, best % forest.width, views.format_distances(best)
+    );
+
+    if let Some(pos) = args.iter().position(|r| r == "--top") {
+        let k: usize = args[pos + 1].parse().unwrap();
+        println!("Top {k} treehouse locations:");
+        for (index, score) in views.top(k) {
+            println!(
+                "row {:>3} col {:>3}: score {score:>8}, {}",
+                index / forest.width, index % forest.width, views.format_distances(index)
+            );
+        }
+    }
+    if let Some(pos) = args.iter().position(|r| r == "--export") {
+        if let Err(e) = export(&args[pos + 1], &views, forest.width) {
+            eprintln!("Export failed: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_forest() -> Forest {
+        let input = read_input(&vec!["--test".to_string()]);
+        let mut forest = Forest::new();
+        for line in input.lines() {
+            forest.add_row(line)
+        }
+        forest
+    }
+
+    #[test]
+    fn test_views() {
+        let views = test_forest().views(&ViewOptions::default());
+        assert_eq!(views.visible_trees(), 21);
+        assert_eq!(views.highest_scenic(), (3 * 5 + 2, 8));
+
+        // Middle 5 on the fourth row, up, left, right, down
+        assert_eq!(views.distances(3 * 5 + 2), &[2, 2, 2, 1]);
+        assert_eq!(views.scenic[3 * 5 + 2], 8);
+        assert!(!views.visible[2 * 5 + 2]);
+    }
+
+    #[test]
+    fn test_naive_views() {
+        let options = [
+            ViewOptions::default(),
+            ViewOptions { diagonals: true, ..Default::default() },
+            ViewOptions { tolerance: 2, ..Default::default() },
+            ViewOptions { diagonals: true, tolerance: 1, max_distance: Some(3) },
+            ViewOptions { diagonals: true, tolerance: 0, max_distance: Some(2) },
+        ];
+        for forest in [Forest::generate(30, 20, 1), Forest::slope(15)] {
+            for options in &options {
+                let views = forest.views(options);
+                for row in 0..forest.height() {
+                    for col in 0..forest.width {
+                        let index = forest.width * row + col;
+                        assert_eq!(
+                            (views.visible[index], views.scenic[index]),
+                            forest.naive_view(row, col, options),
+                            "{options:?} row {row} col {col}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_exports() {
+        let forest = test_forest();
+        let views = forest.views(&ViewOptions::default());
+        assert_eq!(views.top(2), vec![(3 * 5 + 2, 8), (2 * 5 + 1, 6)]);
+
+        let csv = matrix_csv(&views.scenic, forest.width);
+        assert_eq!(csv.lines().nth(3), Some("0,1,8,3,0"));
+        let image = matrix_pgm(&views.scenic, forest.width);
+        assert!(image.starts_with(b"P5\n5 5\n255\n"));
+        assert_eq!(image.len(), b"P5\n5 5\n255\n".len() + 25);
+        assert_eq!(image[b"P5\n5 5\n255\n".len() + 17], 255);
+
+        let image = matrix_pgm(&[usize::MAX, usize::MAX / 2, 0], 3);
+        assert_eq!(image[b"P5\n3 1\n255\n".len()..], [255, 127, 0]);
+        let forest = Forest::slope(300);
+        let views = forest.views(&ViewOptions { diagonals: true, tolerance: 1000, max_distance: None });
+        // Multiplying these scores by 255 would overflow
+        assert!(views.highest_scenic().1 > usize::MAX / 255);
+        let image = matrix_pgm(&views.scenic, forest.width);
+        assert_eq!(image.iter().rev().take(300 * 300).max(), Some(&255));
+    }
 }
@@ -1,107 +1,675 @@
-use std::env;
-use std::collections::HashSet;
+use std::{env, fmt, fs, time::Instant};
+use std::collections::{HashMap, HashSet};
 
 use log::debug;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 use utils::{read_input, set_logging_level};
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 struct Knot {
     x: isize,
     y: isize
 }
 
-struct Bridge {
-    knots: Vec<Knot>,
-    positions: HashSet<Knot>
+/// Head move directions and their x and y steps, up is positive y
+const DIRECTIONS: [(&str, isize, isize); 8] = [
+    ("U", 0, 1), ("D", 0, -1), ("L", -1, 0), ("R", 1, 0),
+    ("UL", -1, 1), ("UR", 1, 1), ("DL", -1, -1), ("DR", 1, -1)
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Move {
+    direction: &'static str,
+    count: usize
 }
 
-impl Bridge {
-    pub fn new(n_knots: usize) -> Bridge {
-        let mut knots: Vec<Knot> = Vec::with_capacity(n_knots);
-        for _ in 0..(n_knots) {
-            knots.push(Knot { x: 0, y: 0 })
-        }
+impl Move {
+    fn delta(&self) -> (isize, isize) {
+        let (_, dx, dy) = DIRECTIONS.iter().find(|(name, _, _)| *name == self.direction).unwrap();
+        (*dx, *dy)
+    }
+}
 
-        Bridge {
-            knots: knots,
-            positions: HashSet::from([Knot { x: 0, y: 0 }])
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.direction, self.count)
+    }
+}
+
+/// Move list that cannot be parsed, `line` is the line number in the input file
+#[derive(Debug, PartialEq, Eq)]
+struct MoveError {
+    line: usize,
+    reason: String
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.reason)
+    }
+}
+
+fn parse_count(count: &str, line: usize) -> Result<usize, MoveError> {
+    count.parse().map_err(|_| MoveError { line, reason: format!("Invalid count {count:?}") })
+}
+
+/// Parses moves like `UL 3`, blocks between `repeat N` and `end` are
+/// repeated N times and can be nested
+fn parse_moves(input: &str) -> Result<Vec<Move>, MoveError> {
+    // Open blocks as (line, repeat count, moves)
+    let mut blocks: Vec<(usize, usize, Vec<Move>)> = vec![(0, 1, Vec::new())];
+    for (index, line) in input.lines().enumerate() {
+        let line_number = index + 1;
+        let split: Vec<&str> = line.split_whitespace().collect();
+        match split[..] {
+            [] => continue,
+            ["repeat", count] => blocks.push((line_number, parse_count(count, line_number)?, Vec::new())),
+            ["end"] => {
+                if blocks.len() == 1 {
+                    return Err(MoveError { line: line_number, reason: "`end` without `repeat`".to_string() });
+                }
+                let (_, count, body) = blocks.pop().unwrap();
+                let outer = &mut blocks.last_mut().unwrap().2;
+                for _ in 0..count {
+                    outer.extend(body.iter().cloned());
+                }
+            },
+            [direction, count] => {
+                let direction = match DIRECTIONS.iter().find(|(name, _, _)| *name == direction) {
+                    Some((name, _, _)) => *name,
+                    None => return Err(MoveError {
+                        line: line_number,
+                        reason: format!("Unknown direction {direction:?}")
+                    })
+                };
+                let count = parse_count(count, line_number)?;
+                blocks.last_mut().unwrap().2.push(Move { direction, count });
+            },
+            _ => return Err(MoveError {
+                line: line_number,
+                reason: format!("Expected \"DIRECTION COUNT\", \"repeat N\" or \"end\", got {line:?}")
+            })
         }
     }
+    match blocks.pop() {
+        Some((0, _, moves)) => Ok(moves),
+        Some((line, _, _)) => Err(MoveError { line, reason: "`repeat` without `end`".to_string() }),
+        None => unreachable!()
+    }
+}
 
-    fn drag(&mut self, i: usize) {
-        let x_distance: isize = self.knots[i - 1].x - self.knots[i].x;
-        let y_distance: isize = self.knots[i - 1].y - self.knots[i].y;
+/// How a knot moves after the knot in front of it has moved
+trait FollowRule {
+    fn name(&self) -> String;
+
+    /// New position of `knot`
+    fn follow(&self, leader: Knot, knot: Knot) -> Knot;
+
+    /// Whether diagonal head moves are split into a horizontal and a vertical step
+    fn axis_steps(&self) -> bool {
+        false
+    }
+}
+
+/// Puzzle rules, moves one step straight or diagonally when not touching
+struct Standard;
+
+impl FollowRule for Standard {
+    fn name(&self) -> String {
+        "standard".to_string()
+    }
+
+    fn follow(&self, leader: Knot, mut knot: Knot) -> Knot {
+        let x_distance: isize = leader.x - knot.x;
+        let y_distance: isize = leader.y - knot.y;
         debug!("distance: {}, {}", x_distance, y_distance);
 
         if (x_distance.abs() < 2) & (y_distance.abs() < 2) {
-            return;
-        } else if (x_distance.abs() > 1) & (y_distance.abs() == 0) {
-            self.knots[i].x += 1 * x_distance.signum();
+            return knot;
+        }
+        if (x_distance.abs() > 1) & (y_distance.abs() == 0) {
+            knot.x += x_distance.signum();
         } else if (y_distance.abs() > 1) & (x_distance.abs() == 0) {
-            self.knots[i].y += 1 * y_distance.signum();
+            knot.y += y_distance.signum();
         } else {
-            self.knots[i].x += 1 * x_distance.signum();
-            self.knots[i].y += 1 * y_distance.signum();
+            knot.x += x_distance.signum();
+            knot.y += y_distance.signum();
         }
+        knot
+    }
+}
+
+/// Lets the rope stretch `slack` cells before the knot follows
+struct Elastic {
+    slack: isize
+}
+
+impl FollowRule for Elastic {
+    fn name(&self) -> String {
+        format!("elastic with slack {}", self.slack)
+    }
+
+    fn follow(&self, leader: Knot, knot: Knot) -> Knot {
+        let x_distance: isize = leader.x - knot.x;
+        let y_distance: isize = leader.y - knot.y;
+        if x_distance.abs().max(y_distance.abs()) <= 1 + self.slack {
+            return knot;
+        }
+        Knot { x: knot.x + x_distance.signum(), y: knot.y + y_distance.signum() }
+    }
+}
+
+/// Knots only touch side by side and never move diagonally. A knot that no longer
+/// touches its leader takes one step along the longer axis. Diagonal head moves
+/// are split in two, so every knot moves at most one cell per step and all the
+/// cells it passes are visited.
+struct FourConnected;
+
+impl FollowRule for FourConnected {
+    fn name(&self) -> String {
+        "4-connected".to_string()
+    }
+
+    fn follow(&self, leader: Knot, mut knot: Knot) -> Knot {
+        let x_distance: isize = leader.x - knot.x;
+        let y_distance: isize = leader.y - knot.y;
+        if x_distance.abs() + y_distance.abs() <= 1 {
+            return knot;
+        }
+        if x_distance.abs() >= y_distance.abs() {
+            knot.x += x_distance.signum();
+        } else {
+            knot.y += y_distance.signum();
+        }
+        knot
+    }
+
+    fn axis_steps(&self) -> bool {
+        true
+    }
+}
+
+/// Set of positions a knot has visited
+trait VisitedSet: Default {
+    /// Returns true if the position was not visited before
+    fn insert(&mut self, knot: Knot) -> bool;
+
+    fn contains(&self, knot: &Knot) -> bool;
+
+    fn len(&self) -> usize;
+}
+
+impl VisitedSet for HashSet<Knot> {
+    fn insert(&mut self, knot: Knot) -> bool {
+        HashSet::insert(self, knot)
+    }
+
+    fn contains(&self, knot: &Knot) -> bool {
+        HashSet::contains(self, knot)
+    }
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+}
+
+/// Side length of a bitmap chunk in cells, a row of a chunk is one `u64`
+const CHUNK_SIZE: isize = 64;
+
+type Chunk = [u64; CHUNK_SIZE as usize];
+
+/// Bitmap of visited cells split into square chunks. Only visited chunks are
+/// stored, so memory grows with the visited cells rather than the area the rope
+/// covers. Consecutive cells are nearly always in the same chunk, which is
+/// cached so that most inserts skip hashing.
+#[derive(Default)]
+struct ChunkedBitmap {
+    /// Index in `chunks` by chunk coordinates
+    index: HashMap<(isize, isize), usize>,
+    chunks: Vec<Chunk>,
+    /// Coordinates and index of the chunk used last
+    last: Option<((isize, isize), usize)>,
+    count: usize
+}
+
+impl ChunkedBitmap {
+    /// Chunk coordinates and the cell within the chunk
+    fn locate(knot: &Knot) -> ((isize, isize), usize, usize) {
+        let key = (knot.x.div_euclid(CHUNK_SIZE), knot.y.div_euclid(CHUNK_SIZE));
+        (key, knot.x.rem_euclid(CHUNK_SIZE) as usize, knot.y.rem_euclid(CHUNK_SIZE) as usize)
+    }
+
+    fn chunk_index(&self, key: (isize, isize)) -> Option<usize> {
+        match self.last {
+            Some((last, index)) if last == key => Some(index),
+            _ => self.index.get(&key).copied()
+        }
+    }
+}
+
+impl VisitedSet for ChunkedBitmap {
+    fn insert(&mut self, knot: Knot) -> bool {
+        let (key, x, y) = ChunkedBitmap::locate(&knot);
+        let index = match self.chunk_index(key) {
+            Some(index) => index,
+            None => {
+                self.chunks.push([0; CHUNK_SIZE as usize]);
+                self.index.insert(key, self.chunks.len() - 1);
+                self.chunks.len() - 1
+            }
+        };
+        self.last = Some((key, index));
+        let row = &mut self.chunks[index][y];
+        let mask: u64 = 1 << x;
+        if *row & mask != 0 {
+            return false;
+        }
+        *row |= mask;
+        self.count += 1;
+        true
+    }
+
+    fn contains(&self, knot: &Knot) -> bool {
+        let (key, x, y) = ChunkedBitmap::locate(knot);
+        self.chunk_index(key).is_some_and(|index| self.chunks[index][y] & (1 << x) != 0)
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+}
+
+struct Bridge<S: VisitedSet = ChunkedBitmap> {
+    knots: Vec<Knot>,
+    rule: Box<dyn FollowRule>,
+    /// Positions visited by each knot
+    visited: Vec<S>,
+    /// Every knot after every step, the first entry is the starting position
+    history: Vec<Vec<Knot>>,
+    /// History is only kept when needed, long runs would not fit in memory
+    keep_history: bool,
+    /// Moves and the history index after the last step of each
+    move_ends: Vec<(Move, usize)>,
+    /// Smallest and largest coordinates any knot has been at, kept without history too
+    bounds: (Knot, Knot)
+}
+
+impl Bridge {
+    pub fn new(n_knots: usize) -> Bridge {
+        Bridge::with_rule(n_knots, Box::new(Standard))
     }
 
-    fn step_up(&mut self) {
-        self.knots[0].y += 1;
+    pub fn with_rule(n_knots: usize, rule: Box<dyn FollowRule>) -> Bridge {
+        Bridge::with_visited_set(n_knots, rule)
     }
+}
 
-    fn step_down(&mut self) {
-        self.knots[0].y -= 1;
+impl<S: VisitedSet> Bridge<S> {
+    pub fn with_visited_set(n_knots: usize, rule: Box<dyn FollowRule>) -> Bridge<S> {
+        let knots: Vec<Knot> = vec![Knot { x: 0, y: 0 }; n_knots];
+        let visited: Vec<S> = (0..n_knots)
+            .map(|_| {
+                let mut visited = S::default();
+                visited.insert(Knot { x: 0, y: 0 });
+                visited
+            })
+            .collect();
+
+        Bridge {
+            history: vec![knots.clone()],
+            keep_history: true,
+            knots,
+            rule,
+            visited,
+            move_ends: Vec::new(),
+            bounds: (Knot { x: 0, y: 0 }, Knot { x: 0, y: 0 })
+        }
     }
 
-    fn step_left(&mut self) {
-        self.knots[0].x -= 1;
+    pub fn without_history(mut self) -> Bridge<S> {
+        self.keep_history = false;
+        self.history.clear();
+        self
     }
 
-    fn step_right(&mut self) {
-        self.knots[0].x += 1;
+    fn drag(&mut self, i: usize) {
+        self.knots[i] = self.rule.follow(self.knots[i - 1], self.knots[i]);
     }
 
-    pub fn process_line(&mut self, line: &str) {
-        debug!("{line}");
-        let split: Vec<&str> = line.split(" ").collect();
+    /// Moves the head one step and the other knots after it
+    fn step(&mut self, dx: isize, dy: isize) {
+        self.knots[0].x += dx;
+        self.knots[0].y += dy;
 
-        let step = match split[0] {
-            "U" => Bridge::step_up,
-            "D" => Bridge::step_down,
-            "L" => Bridge::step_left,
-            "R" => Bridge::step_right,
-            &_ => panic!("Unknown direction: {}", split[0])
+        for i in 1..self.knots.len() {
+            self.drag(i);
+        }
+        debug!("Tail: {}, {}", self.knots.last().unwrap().x, self.knots.last().unwrap().y);
+        let (min, max) = &mut self.bounds;
+        for (visited, knot) in self.visited.iter_mut().zip(&self.knots) {
+            visited.insert(*knot);
+            *min = Knot { x: min.x.min(knot.x), y: min.y.min(knot.y) };
+            *max = Knot { x: max.x.max(knot.x), y: max.y.max(knot.y) };
+        }
+        if self.keep_history {
+            self.history.push(self.knots.clone());
+        }
+    }
+
+    pub fn apply(&mut self, m: &Move) {
+        debug!("{m}");
+        let (dx, dy) = m.delta();
+        let steps = if self.rule.axis_steps() && dx != 0 && dy != 0 {
+            vec![(dx, 0), (0, dy)]
+        } else {
+            vec![(dx, dy)]
         };
-        let count: usize = split[1].parse().unwrap();
 
-        for _ in 0..count {
-            step(self);
+        for _ in 0..m.count {
+            for (dx, dy) in &steps {
+                self.step(*dx, *dy);
+            }
+        }
+        if self.keep_history {
+            self.move_ends.push((m.clone(), self.history.len() - 1));
+        }
+    }
+
+    pub fn run(&mut self, moves: &[Move]) {
+        for m in moves {
+            self.apply(m);
+        }
+    }
+
+    /// Number of positions visited by each knot, a knot only depends on the
+    /// knots in front of it so knot `i` gives the answer for a rope of `i + 1` knots
+    pub fn visited_counts(&self) -> Vec<usize> {
+        self.visited.iter().map(|visited| visited.len()).collect()
+    }
 
-            for i in 1..self.knots.len() {
-                self.drag(i);
+    /// Label used in the puzzle illustrations, H for the head and T for the
+    /// tail of a two knot rope, otherwise the knot number
+    fn label(&self, i: usize) -> char {
+        match i {
+            0 => 'H',
+            1 if self.knots.len() == 2 => 'T',
+            _ => char::from_digit(i as u32, 36).unwrap_or('+')
+        }
+    }
+
+    /// Rows from the top of the bounds, `cell` returns None for an empty cell
+    fn render<F: Fn(Knot) -> Option<char>>(&self, cell: F) -> String {
+        let (min, max) = self.bounds;
+        let mut rows: Vec<String> = Vec::new();
+        for y in (min.y..=max.y).rev() {
+            rows.push((min.x..=max.x).map(|x| cell(Knot { x, y }).unwrap_or('.')).collect());
+        }
+        rows.join("\n")
+    }
+
+    /// Rope after step `step`, knots closer to the head are drawn on top
+    pub fn render_frame(&self, step: usize) -> String {
+        let knots = &self.history[step];
+        self.render(|position| {
+            match knots.iter().position(|knot| *knot == position) {
+                Some(i) => Some(self.label(i)),
+                None if position == (Knot { x: 0, y: 0 }) => Some('s'),
+                None => None
+            }
+        })
+    }
+
+    /// Cells visited by the tail
+    pub fn render_trail(&self) -> String {
+        self.render(|position| {
+            if position == (Knot { x: 0, y: 0 }) {
+                Some('s')
+            } else if self.visited.last().unwrap().contains(&position) {
+                Some('#')
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Position of every knot after every step as `step,knot,x,y` rows
+    pub fn history_csv(&self) -> String {
+        let mut rows: Vec<String> = vec!["step,knot,x,y".to_string()];
+        for (step, knots) in self.history.iter().enumerate() {
+            for (i, knot) in knots.iter().enumerate() {
+                rows.push(format!("{step},{i},{},{}", knot.x, knot.y));
             }
-            debug!("Tail: {}, {}", self.knots.last().unwrap().x, self.knots.last().unwrap().y);
-            self.positions.insert(*self.knots.last().unwrap());
         }
+        rows.join("\n")
     }
 }
 
+fn generate_moves(count: usize, seed: u64) -> Vec<Move> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..count)
+        .map(|_| Move {
+            direction: DIRECTIONS[rng.gen_range(0..4)].0,
+            count: rng.gen_range(1..=20)
+        })
+        .collect()
+}
+
+fn time_visited_set<S: VisitedSet>(name: &str, moves: &[Move], n_knots: usize) -> Vec<usize> {
+    let start = Instant::now();
+    let mut bridge: Bridge<S> = Bridge::with_visited_set(n_knots, Box::new(Standard)).without_history();
+    bridge.run(moves);
+    let counts = bridge.visited_counts();
+    println!("{name}: tail visited {} positions in {:?}", counts[n_knots - 1], start.elapsed());
+    counts
+}
+
+/// Compares the chunked bitmap to a `HashSet` on generated moves with every knot tracked
+fn benchmark(count: usize) {
+    let moves = generate_moves(count, 0);
+    let steps: usize = moves.iter().map(|m| m.count).sum();
+    println!("Benchmark with {count} generated moves, {steps} steps of 10 knots");
+    let hash_counts = time_visited_set::<HashSet<Knot>>("HashSet", &moves, 10);
+    let bitmap_counts = time_visited_set::<ChunkedBitmap>("Chunked bitmap", &moves, 10);
+    assert_eq!(hash_counts, bitmap_counts);
+}
+
+fn parse_rule(args: &[String]) -> Result<Option<Box<dyn FollowRule>>, String> {
+    let pos = match args.iter().position(|r| r == "--follow") {
+        Some(pos) => pos,
+        None => return Ok(None)
+    };
+    let rule: Box<dyn FollowRule> = match args.get(pos + 1).map(|name| name.as_str()) {
+        Some("standard") => Box::new(Standard),
+        Some("elastic") => match args.get(pos + 2).and_then(|slack| slack.parse().ok()) {
+            Some(slack) => Box::new(Elastic { slack }),
+            None => return Err("`--follow elastic` needs the slack as a number".to_string())
+        },
+        Some("four") => Box::new(FourConnected),
+        Some(other) => return Err(format!("Unknown follow rule {other:?}, expected standard, elastic K or four")),
+        None => return Err("Missing follow rule after --follow".to_string())
+    };
+    Ok(Some(rule))
+}
+
+/// Prints the error and exits instead of panicking
+fn exit_on_error<T>(result: Result<T, String>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn report(bridge: &Bridge, args: &[String]) {
+    let n_knots = bridge.knots.len();
+    if args.contains(&String::from("--frames")) {
+        println!("== Initial State ==\n{}\n", bridge.render_frame(0));
+        for (m, step) in &bridge.move_ends {
+            println!("== {m} ==\n{}\n", bridge.render_frame(*step));
+        }
+    }
+    if args.contains(&String::from("--trail")) {
+        println!("Tail trail with {n_knots} knots:\n{}\n", bridge.render_trail());
+    }
+    if let Some(pos) = args.iter().position(|r| r == "--csv") {
+        let path = format!("{}_{n_knots}.csv", args[pos + 1]);
+        fs::write(&path, bridge.history_csv()).unwrap();
+        println!("Wrote knot history to {path}");
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     set_logging_level(&args);
+
+    if let Some(pos) = args.iter().position(|r| r == "--bench") {
+        benchmark(args[pos + 1].parse().unwrap());
+        return;
+    }
+
     let input = read_input(&args);
+    let moves = match parse_moves(&input) {
+        Ok(moves) => moves,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let n_knots: usize = match args.iter().position(|r| r == "--knots") {
+        Some(pos) => exit_on_error(
+            args.get(pos + 1).and_then(|n| n.parse().ok()).ok_or("`--knots` needs a number".to_string())
+        ),
+        None => 10
+    };
+    if n_knots < 2 {
+        eprintln!("A rope needs at least 2 knots, got {n_knots}");
+        std::process::exit(1);
+    }
 
-    let mut bridge_2 = Bridge::new(2);
-    for line in input.lines() {
-        bridge_2.process_line(line)
+    // Every knot of a single long rope is tracked at once
+    let mut bridge = match exit_on_error(parse_rule(&args)) {
+        Some(rule) => Bridge::with_rule(n_knots, rule),
+        None => Bridge::new(n_knots)
+    };
+    if !["--frames", "--csv"].iter().any(|flag| args.contains(&flag.to_string())) {
+        bridge = bridge.without_history();
+    }
+    bridge.run(&moves);
+    report(&bridge, &args);
+    println!("Follow rule: {}", bridge.rule.name());
+    let counts = bridge.visited_counts();
+    if n_knots > 2 {
+        println!("Visited positions with 2 knots: {}", counts[1]);
     }
-    println!("Visited positions with 2 knots: {}", bridge_2.positions.len());
+    println!("Visited positions with {n_knots} knots: {}", counts[n_knots - 1]);
+    if args.contains(&String::from("--counts")) {
+        for (i, count) in counts.iter().enumerate() {
+            println!("Knot {i}: {count}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_bridge(n_knots: usize) -> Bridge {
+        let input = read_input(&vec!["--test".to_string()]);
+        let mut bridge = Bridge::new(n_knots);
+        bridge.run(&parse_moves(&input).unwrap());
+        bridge
+    }
+
+    #[test]
+    fn test_visited() {
+        assert_eq!(test_bridge(2).visited_counts(), vec![21, 13]);
+        let counts = test_bridge(10).visited_counts();
+        assert_eq!((counts[1], counts[9]), (13, 1));
+    }
+
+    #[test]
+    fn test_moves_and_rules() {
+        let moves = parse_moves("repeat 2\nUR 3\nrepeat 2\nL 1\nend\nend\nD 1").unwrap();
+        let directions: Vec<&str> = moves.iter().map(|m| m.direction).collect();
+        assert_eq!(directions, vec!["UR", "L", "L", "UR", "L", "L", "D"]);
+        assert_eq!(parse_moves("R 1\nrepeat 2\nU 1").unwrap_err().line, 2);
+        assert_eq!(parse_moves("end").unwrap_err().reason, "`end` without `repeat`");
+        assert_eq!(parse_moves("X 1").unwrap_err().reason, "Unknown direction \"X\"");
+
+        let ur = [Move { direction: "UR", count: 3 }];
+        let mut bridge = Bridge::with_rule(2, Box::new(FourConnected));
+        bridge.run(&ur);
+        assert_eq!(bridge.knots[1], Knot { x: 3, y: 2 });
+        // The head passes (1, 0), (2, 1) and (3, 2), the tail every cell in between
+        assert_eq!(bridge.visited_counts(), vec![7, 6]);
+        assert!(bridge.visited[1].contains(&Knot { x: 2, y: 0 }));
+
+        let mut bridge = Bridge::with_rule(2, Box::new(Elastic { slack: 1 }));
+        bridge.run(&[Move { direction: "R", count: 4 }]);
+        assert_eq!(bridge.knots[1], Knot { x: 2, y: 0 });
+        assert_eq!(bridge.visited_counts(), vec![5, 3]);
+
+        let mut bridge = Bridge::new(2);
+        bridge.run(&ur);
+        assert_eq!(bridge.knots[1], Knot { x: 2, y: 2 });
+    }
+
+    #[test]
+    fn test_rendering() {
+        let bridge = test_bridge(2);
+        assert_eq!(bridge.history.len(), 25);
+        assert_eq!(bridge.render_frame(bridge.move_ends[0].1), "\
+            ......\n......\n......\n......\ns..TH.");
+        assert_eq!(bridge.render_trail(), "\
+            ..##..\n...##.\n.####.\n....#.\ns###..");
+
+        let csv = bridge.history_csv();
+        assert_eq!(csv.lines().count(), 1 + 25 * 2);
+        assert_eq!(csv.lines().nth(7), Some("3,0,3,0"));
+
+        // The trail doesn't need the history
+        let mut bridge = Bridge::new(2).without_history();
+        bridge.run(&parse_moves(&read_input(&vec!["--test".to_string()])).unwrap());
+        assert_eq!(bridge.render_trail(), "\
+            ..##..\n...##.\n.####.\n....#.\ns###..");
+
+        let bridge = test_bridge(10);
+        // Knots under the head are hidden like in the puzzle text
+        assert_eq!(bridge.render_frame(bridge.move_ends[0].1).lines().last(), Some("4321H."));
+    }
+
+    #[test]
+    fn test_chunked_bitmap() {
+        let mut bitmap = ChunkedBitmap::default();
+        let mut set: HashSet<Knot> = HashSet::new();
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..5000 {
+            let knot = Knot { x: rng.gen_range(-500..500), y: rng.gen_range(-300..700) };
+            assert_eq!(VisitedSet::insert(&mut bitmap, knot), set.insert(knot));
+        }
+        assert_eq!(VisitedSet::len(&bitmap), set.len());
+        assert!(set.iter().all(|knot| VisitedSet::contains(&bitmap, knot)));
+        assert!(!VisitedSet::contains(&bitmap, &Knot { x: 10_000, y: -10_000 }));
+
+        // Chunks are only allocated along the path of long runs
+        for (dx, dy) in [(1, 0), (1, 1), (-1, 1)] {
+            let mut bitmap = ChunkedBitmap::default();
+            for i in 0..200_000 {
+                VisitedSet::insert(&mut bitmap, Knot { x: i * dx, y: i * dy });
+            }
+            assert_eq!(VisitedSet::len(&bitmap), 200_000);
+            assert!(bitmap.chunks.len() <= 2 * (200_000 / CHUNK_SIZE as usize + 1));
+        }
 
-    let mut bridge_10 = Bridge::new(10);
-    for line in input.lines() {
-        bridge_10.process_line(line)
+        let moves = generate_moves(200, 3);
+        let mut bridge: Bridge<HashSet<Knot>> = Bridge::with_visited_set(10, Box::new(Standard));
+        bridge.run(&moves);
+        let mut bitmap_bridge = Bridge::new(10).without_history();
+        bitmap_bridge.run(&moves);
+        assert_eq!(bridge.visited_counts(), bitmap_bridge.visited_counts());
     }
-    println!("Visited positions with 10 knots: {}", bridge_10.positions.len());
 }
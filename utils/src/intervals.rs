@@ -0,0 +1,352 @@
+use std::fmt;
+
+/// Inclusive range of integers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval {
+    pub start: i64,
+    pub end: i64
+}
+
+impl Interval {
+    pub fn new(start: i64, end: i64) -> Interval {
+        if start > end {
+            panic!("Interval start {start} is after end {end}")
+        }
+        Interval { start, end }
+    }
+
+    /// Number of integers in the interval, `u128` fits the whole `i64` range
+    pub fn size(&self) -> u128 {
+        self.start.abs_diff(self.end) as u128 + 1
+    }
+
+    pub fn contains_value(&self, value: i64) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn intersects(&self, other: &Interval) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Option<Interval> {
+        if self.intersects(other) {
+            Some(Interval::new(self.start.max(other.start), self.end.min(other.end)))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Set of integers stored as sorted, disjoint and non-adjacent intervals
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>
+}
+
+impl IntervalSet {
+    pub fn new() -> IntervalSet {
+        IntervalSet { intervals: Vec::new() }
+    }
+
+    /// Builds a set from possibly overlapping intervals, merging them
+    pub fn from_intervals<I: IntoIterator<Item = Interval>>(intervals: I) -> IntervalSet {
+        let mut sorted: Vec<Interval> = intervals.into_iter().collect();
+        sorted.sort();
+        let mut merged: Vec<Interval> = Vec::with_capacity(sorted.len());
+        for interval in sorted {
+            match merged.last_mut() {
+                Some(last) if interval.start <= last.end.saturating_add(1) => {
+                    last.end = last.end.max(interval.end);
+                },
+                _ => merged.push(interval)
+            }
+        }
+        IntervalSet { intervals: merged }
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Number of integers in the set
+    pub fn len(&self) -> u128 {
+        self.intervals.iter().map(|i| i.size()).sum()
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        match self.intervals.binary_search_by(|i| i.start.cmp(&value)) {
+            Ok(_) => true,
+            Err(0) => false,
+            Err(index) => self.intervals[index - 1].contains_value(value)
+        }
+    }
+
+    pub fn insert(&mut self, interval: Interval) {
+        let mut intervals = std::mem::take(&mut self.intervals);
+        intervals.push(interval);
+        *self = IntervalSet::from_intervals(intervals);
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        IntervalSet::from_intervals(self.intervals.iter().chain(&other.intervals).copied())
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut intervals: Vec<Interval> = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (&self.intervals[i], &other.intervals[j]);
+            if let Some(common) = a.intersection(b) {
+                intervals.push(common);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { intervals }
+    }
+
+    pub fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        match (self.intervals.first(), self.intervals.last()) {
+            (Some(first), Some(last)) => {
+                let bounds = Interval::new(first.start, last.end);
+                self.intersection(&other.gaps(bounds))
+            },
+            _ => IntervalSet::new()
+        }
+    }
+
+    /// Integers within `bounds` that are not in the set
+    pub fn gaps(&self, bounds: Interval) -> IntervalSet {
+        let mut intervals: Vec<Interval> = Vec::new();
+        let mut next: i64 = bounds.start;
+        for interval in &self.intervals {
+            if interval.end < next {
+                continue;
+            }
+            if interval.start > bounds.end {
+                break;
+            }
+            if interval.start > next {
+                intervals.push(Interval::new(next, interval.start - 1));
+            }
+            match interval.end.checked_add(1) {
+                Some(value) => next = value,
+                None => return IntervalSet { intervals }
+            }
+        }
+        if next <= bounds.end {
+            intervals.push(Interval::new(next, bounds.end));
+        }
+        IntervalSet { intervals }
+    }
+
+    /// Integers covered by at least `count` of the intervals
+    pub fn covered_at_least(intervals: &[Interval], count: usize) -> IntervalSet {
+        if count == 0 {
+            return IntervalSet::from_intervals([Interval::new(i64::MIN, i64::MAX)]);
+        }
+        // Sweep over interval starts and ends, an end event happens right after the last value
+        let mut events: Vec<(i64, i64)> = Vec::with_capacity(intervals.len() * 2);
+        for interval in intervals {
+            events.push((interval.start, 1));
+            if let Some(after) = interval.end.checked_add(1) {
+                events.push((after, -1));
+            }
+        }
+        events.sort();
+
+        let mut result: Vec<Interval> = Vec::new();
+        let mut depth: i64 = 0;
+        let mut index: usize = 0;
+        while index < events.len() {
+            let position = events[index].0;
+            while index < events.len() && events[index].0 == position {
+                depth += events[index].1;
+                index += 1;
+            }
+            if depth >= count as i64 {
+                let end = match events.get(index) {
+                    Some((next, _)) => next - 1,
+                    None => i64::MAX
+                };
+                result.push(Interval::new(position, end));
+            }
+        }
+        IntervalSet::from_intervals(result)
+    }
+}
+
+/// Static interval tree, entries are sorted by start and form an implicit
+/// balanced tree where every node knows the largest end in its subtree
+#[derive(Debug, Clone)]
+pub struct IntervalTree<T> {
+    entries: Vec<(Interval, T)>,
+    max_end: Vec<i64>
+}
+
+impl<T> IntervalTree<T> {
+    pub fn new(mut entries: Vec<(Interval, T)>) -> IntervalTree<T> {
+        entries.sort_by_key(|(interval, _)| *interval);
+        let mut tree = IntervalTree { max_end: vec![i64::MIN; entries.len()], entries };
+        tree.build(0, tree.entries.len());
+        tree
+    }
+
+    fn build(&mut self, low: usize, high: usize) -> i64 {
+        if low >= high {
+            return i64::MIN;
+        }
+        let mid = (low + high) / 2;
+        let max_end = self.entries[mid].0.end
+            .max(self.build(low, mid))
+            .max(self.build(mid + 1, high));
+        self.max_end[mid] = max_end;
+        max_end
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries ordered by interval start
+    pub fn entries(&self) -> &[(Interval, T)] {
+        &self.entries
+    }
+
+    /// Entries overlapping the query, ordered by interval start
+    pub fn overlapping(&self, query: &Interval) -> Vec<&(Interval, T)> {
+        let mut found = Vec::new();
+        self.query(0, self.entries.len(), query, &mut found);
+        found
+    }
+
+    fn query<'a>(&'a self, low: usize, high: usize, query: &Interval, found: &mut Vec<&'a (Interval, T)>) {
+        if low >= high {
+            return;
+        }
+        let mid = (low + high) / 2;
+        if self.max_end[mid] < query.start {
+            return;
+        }
+        self.query(low, mid, query, found);
+        let entry = &self.entries[mid];
+        if entry.0.start > query.end {
+            return;
+        }
+        if entry.0.intersects(query) {
+            found.push(entry);
+        }
+        self.query(mid + 1, high, query, found);
+    }
+
+    /// Number of entries after entry `index` that overlap it
+    fn overlapping_after(&self, index: usize) -> usize {
+        let end = self.entries[index].0.end;
+        let after = &self.entries[index + 1..];
+        after.partition_point(|(interval, _)| interval.start <= end)
+    }
+
+    /// Every overlapping pair of entries
+    pub fn overlapping_pairs(&self) -> Vec<(&T, &T)> {
+        let mut pairs = Vec::new();
+        for index in 0..self.entries.len() {
+            let count = self.overlapping_after(index);
+            for other in &self.entries[index + 1..index + 1 + count] {
+                pairs.push((&self.entries[index].1, &other.1));
+            }
+        }
+        pairs
+    }
+
+    pub fn count_overlapping_pairs(&self) -> usize {
+        (0..self.entries.len()).map(|index| self.overlapping_after(index)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_intervals_merges() {
+        let set = IntervalSet::from_intervals([
+            Interval::new(5, 7), Interval::new(1, 2), Interval::new(3, 3), Interval::new(6, 10)
+        ]);
+        assert_eq!(set.intervals(), &[Interval::new(1, 3), Interval::new(5, 10)]);
+        assert_eq!(set.len(), 9);
+        assert_eq!(IntervalSet::covered_at_least(&[], 0).len(), 1 << 64);
+        assert!(set.contains(3));
+        assert!(!set.contains(4));
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a = IntervalSet::from_intervals([Interval::new(1, 10)]);
+        let b = IntervalSet::from_intervals([Interval::new(3, 4), Interval::new(8, 12)]);
+        assert_eq!(a.union(&b).intervals(), &[Interval::new(1, 12)]);
+        assert_eq!(a.intersection(&b).intervals(), &[Interval::new(3, 4), Interval::new(8, 10)]);
+        assert_eq!(
+            a.difference(&b).intervals(),
+            &[Interval::new(1, 2), Interval::new(5, 7)]
+        );
+        assert_eq!(
+            b.gaps(Interval::new(0, 20)).intervals(),
+            &[Interval::new(0, 2), Interval::new(5, 7), Interval::new(13, 20)]
+        );
+    }
+
+    #[test]
+    fn test_covered_at_least() {
+        let intervals = [Interval::new(2, 4), Interval::new(6, 8), Interval::new(3, 7)];
+        assert_eq!(
+            IntervalSet::covered_at_least(&intervals, 2).intervals(),
+            &[Interval::new(3, 4), Interval::new(6, 7)]
+        );
+        assert!(IntervalSet::covered_at_least(&intervals, 3).is_empty());
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let intervals = [
+            Interval::new(2, 4), Interval::new(6, 8), Interval::new(3, 7),
+            Interval::new(10, 12), Interval::new(1, 1), Interval::new(0, 20)
+        ];
+        let tree = IntervalTree::new(intervals.iter().copied().enumerate().map(|(i, r)| (r, i)).collect());
+        let query = Interval::new(5, 9);
+        let mut found: Vec<usize> = tree.overlapping(&query).iter().map(|(_, i)| *i).collect();
+        found.sort();
+        assert_eq!(found, vec![1, 2, 5]);
+
+        let mut naive = 0;
+        for i in 0..intervals.len() {
+            for j in (i + 1)..intervals.len() {
+                if intervals[i].intersects(&intervals[j]) {
+                    naive += 1;
+                }
+            }
+        }
+        assert_eq!(tree.count_overlapping_pairs(), naive);
+        assert_eq!(tree.overlapping_pairs().len(), naive);
+    }
+}
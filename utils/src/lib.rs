@@ -4,6 +4,8 @@ use std::sync::Once;
 
 use log::{Record, Metadata};
 
+pub mod intervals;
+
 static INIT_LOGGING: Once = Once::new();
 
 static MY_LOGGER: MyLogger = MyLogger;